@@ -0,0 +1,41 @@
+// Bakes the world cities dataset into the crate when the `embedded-data`
+// feature is enabled, so the binaries don't need the CSV at runtime.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const DATA_FILE: &str = "simplemaps-worldcities-basic.csv";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    if env::var_os("CARGO_FEATURE_EMBEDDED_DATA").is_none() {
+        return;
+    }
+
+    println!("cargo:rerun-if-changed={}", DATA_FILE);
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(DATA_FILE)
+        .unwrap_or_else(|e| panic!("Failed to open file {}: {}", DATA_FILE, e));
+
+    let mut out = String::from("pub(crate) static RECORDS: &[EmbeddedRecord] = &[\n");
+    for (index, result) in rdr.records().enumerate() {
+        let record = result.unwrap_or_else(|e| panic!("Invalid city data in {} at row {}: {}", DATA_FILE, index + 1, e));
+        let number = |field: usize| -> f64 {
+            record[field].parse().unwrap_or_else(|e| panic!("Invalid number in {} at row {}: {}", DATA_FILE, index + 1, e))
+        };
+        writeln!(
+            out,
+            "    EmbeddedRecord {{ city: {:?}, city_ascii: {:?}, latitude: {:?}, longitude: {:?}, population: {:?}, country: {:?}, iso2: {:?}, iso3: {:?}, province: {:?} }},",
+            &record[0], &record[1], number(2), number(3), number(4), &record[5], &record[6], &record[7], &record[8],
+        ).unwrap();
+    }
+    out.push_str("];\n");
+
+    let dest = Path::new(&env::var_os("OUT_DIR").unwrap()).join("embedded_cities.rs");
+    fs::write(&dest, out).unwrap_or_else(|e| panic!("Failed to write {}: {}", dest.display(), e));
+}
@@ -1,5 +1,27 @@
-extern crate mastodon_toot_client2;
+extern crate mastodon_toot_bot;
+
+use mastodon_toot_bot::selection::Selection;
+use mastodon_toot_bot::{cli, data_loader, seed, Gazetteer};
 
 fn main() {
-	println!("{}", mastodon_toot_client2::random_location());
-}
\ No newline at end of file
+	let data_path = data_loader::data_path_arg(std::env::args().skip(1));
+	let gazetteer = Gazetteer::load_configured(data_path.as_deref()).unwrap_or_else(|e| panic!("{}", e));
+	let options = cli::text_options(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+	// `--city "Springfield, Illinois"` describes the best match instead of a
+	// random city.
+	let city = match cli::flag_value(std::env::args().skip(1), "--city") {
+		Some(query) => gazetteer.find_city(&query).first().map(|found| found.city).unwrap_or_else(|| panic!("no city matches `{}`", query)),
+		None => {
+			// `--seed <n>`, `--daily` or `--date <date>` make the choice repeatable.
+			let seed = seed::seed_from_args(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+			let selection = Selection::from_args(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+			gazetteer.select_city(&selection, &mut seed::rng(seed)).expect("no cities to choose from")
+		}
+	};
+	// `--format json` prints the location as data instead of sentences.
+	match cli::flag_value(std::env::args().skip(1), "--format").as_deref() {
+		None | Some("text") => println!("{}", gazetteer.location_text_with(city, &options)),
+		Some("json") => println!("{}", serde_json::to_string_pretty(&gazetteer.location(city, &options)).unwrap_or_else(|e| panic!("{}", e))),
+		Some(format) => panic!("unknown format `{}`, expected `text` or `json`", format),
+	}
+}
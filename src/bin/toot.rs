@@ -1,36 +1,63 @@
 // file responsible for sending toot to Mastodon social site...
 
-use mammut::{Data, Mastodon, Registration, status_builder::StatusBuilder, apps::{AppBuilder, Scopes}};
-use mastodon_toot_client2::random_location;
-use dotenv;
+use chrono::{DateTime, Utc};
+use elefren::status_builder::Visibility;
+use elefren::entities::status::Status;
+use elefren::{Data, Mastodon, MastodonClient, Registration, scopes::Scopes};
+use mastodon_toot_bot::compose::ComposeError;
+use mastodon_toot_bot::history::{self, Entry, History, Repeats};
+use mastodon_toot_bot::schedule::{self, Missed, Schedule};
+use mastodon_toot_bot::selection::Selection;
+use mastodon_toot_bot::{cli, compose, data_loader, seed, CityId, Gazetteer, TextOptions};
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 fn main() {
     dotenv::dotenv().ok();
-    if ::std::env::var("CLIENT_ID").is_ok() {
+    // Neither needs an account, so they run before any client is set up.
+    if std::env::args().nth(1).as_deref() == Some("preview") {
+        preview();
+        return;
+    }
+    if cli::has_flag(std::env::args(), "--dry-run") {
+        dry_run();
+        return;
+    }
+
+    let mastodon = if ::std::env::var("CLIENT_ID").is_ok() {
     	println!(">>>>> About to toot using `.env` file ... <<<<<<<<");
-        from_configuration();
+        from_configuration()
     } else {
     	println!(">>>>> About to toot from new client registration ... <<<<<<<<");
-        register_and_post();
+        register()
+    };
+
+    if std::env::args().nth(1).as_deref() == Some("daemon") {
+        daemon(&mastodon);
+    } else {
+        post_once(&mastodon);
     }
+    println!("Done!");
 }
 
-#[allow(dead_code)]
-fn register_and_post() {
-    let app = AppBuilder {
-        client_name: "rust-client",
-        redirect_uris: "urn:ietf:wg:oauth:2.0:oob",
-        scopes: Scopes::ReadWrite,
-        website: None,
-    };
-
+fn register() -> Mastodon {
 	// Register the app using configuration in `.env` file
-    let mut registration = Registration::new(env("BASE"));
-    match registration.register(app) {
-        Ok(_) => println!("App registered successfully"),
+    let registration = Registration::new(env("BASE"))
+        .client_name("rust-client")
+        .redirect_uris("urn:ietf:wg:oauth:2.0:oob")
+        .scopes(Scopes::read_all().and(Scopes::write_all()))
+        .build();
+    let registration = match registration {
+        Ok(registration) => {
+            println!("App registered successfully");
+            registration
+        }
         Err(e) => panic!("Register failed: {}", e),
-    }
-    let url = match registration.authorise() {
+    };
+    let url = match registration.authorize_url() {
         Ok(url) => url,
         Err(e) => panic!("Registration authorize failed: {}", e),
     };
@@ -47,27 +74,15 @@ fn register_and_post() {
     }
     
     // create the app client
-    let mastodon = match registration.create_access_token(code.trim().to_string()) {
+    let mastodon = match registration.complete(code.trim()) {
         Ok(m) => m,
         Err(e) => panic!("Creating access token failed: {}", e),
     };
     println!("{:#?}", mastodon.data);
-    
-    // Lets update status
-    let status = random_location();
-    let sb = StatusBuilder::new(status);
-    println!("StatusBuilder = {:#?}", sb);
-    
-    match mastodon.new_status(sb) {
-        Ok(_) => println!("Status posted successfully"),
-        Err(e) => panic!("Could not post status: {}", e),
-    }
-    
-    println!("Done!");
+    mastodon
 }
 
-#[allow(dead_code)]
-fn from_configuration() {
+fn from_configuration() -> Mastodon {
     let data = Data {
         base: env("BASE").into(),
         client_id: env("CLIENT_ID").into(),
@@ -77,21 +92,430 @@ fn from_configuration() {
     };
 
 	// create mastodon client from configured data
-    let mastodon: Mastodon = Mastodon::from_data(data);
+    Mastodon::from(data)
+}
 
+fn post_once(mastodon: &Mastodon) {
 	// lets get new random status
-    let status = crate::random_location();
-    println!("Posting {}", status);
+    let settings = settings();
+    let gazetteer = gazetteer();
+    let mut history = history();
+    let toot = match random_location(&gazetteer, &settings, &history, Utc::now(), &mut seed::rng(settings.seed)) {
+        Ok(toot) => toot,
+        Err(e) => panic!("{}", e),
+    };
+    for status in &toot.statuses {
+        println!("Posting {}", status);
+    }
 
 	// post status to mastodon
-    let sb = StatusBuilder::new(status);
-    println!("StatusBuilder = {:#?}", sb);
-    match mastodon.new_status(sb) {
-        Ok(_) => println!("Status posted successfully"),
+    match post_statuses(mastodon, toot.statuses, &settings) {
+        Ok(posted) => record(&mut history, toot.city, toot.name, posted),
         Err(e) => panic!("Could not post status: {}", e),
     }
+}
 
-    println!("Done!");
+// `toot daemon` loads everything once and then posts on the schedule from
+// `Schedule::from_args` until it receives SIGTERM or SIGINT. Slots missed
+// while it was down are skipped or caught up on as `--missed` says, judging
+// by the last post in the history.
+fn daemon(mastodon: &Mastodon) {
+    let schedule = schedule();
+    let missed = schedule::missed_arg(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+    let settings = settings();
+    let gazetteer = gazetteer();
+    let mut history = history();
+    // Settings that can never produce a post should stop the daemon now, not
+    // at the first slot hours later.
+    if gazetteer.select_city(&settings.selection, &mut rand::rng()).is_none() {
+        panic!("{}", TootError::NoCity);
+    }
+    // A seed makes the whole sequence of cities repeatable.
+    let mut rng = seed::rng(settings.seed);
+    stop_on_signals();
+
+    let mut slot = first_slot(&schedule, &history, missed);
+    while let Some(due) = slot {
+        seed_slot(&settings, due, &mut rng);
+        let at = due + schedule.jitter(&mut rng);
+        log(&format!("Next post at {}", at.to_rfc3339()));
+        if !sleep_until(at) {
+            break;
+        }
+
+        log("Choosing a city");
+        match random_location(&gazetteer, &settings, &history, Utc::now(), &mut rng) {
+            Ok(toot) => {
+                log(&format!("Posting {} status(es) about {}", toot.statuses.len(), toot.name));
+                match post_statuses(mastodon, toot.statuses, &settings) {
+                    Ok(posted) => {
+                        log(&format!("Posted {}", posted.join(", ")));
+                        record(&mut history, toot.city, toot.name, posted);
+                    }
+                    Err(e) => log(&format!("Could not post status: {}", e)),
+                }
+            }
+            Err(e) => log(&format!("Skipping this post: {}", e)),
+        }
+        slot = schedule.resume(due, Utc::now(), missed);
+    }
+    if slot.is_none() {
+        log("The schedule has no more slots");
+    }
+    log("Stopping");
+}
+
+// With `--daily` each slot is seeded from its own date, so every day gets
+// that day's city instead of the next one in a sequence begun at startup.
+fn seed_slot(settings: &Settings, due: DateTime<Utc>, rng: &mut StdRng) {
+    if settings.daily {
+        *rng = seed::rng(Some(seed::daily_seed(due.date_naive())));
+    }
+}
+
+// The schedule from `Schedule::from_args`, which `toot daemon` requires.
+fn schedule() -> Schedule {
+    match Schedule::from_args(std::env::args().skip(1)) {
+        Ok(Some(schedule)) => schedule,
+        Ok(None) => panic!("`toot daemon` needs `--schedule`, `--every`, `TOOT_SCHEDULE` or `TOOT_EVERY`"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+// Picks up from the last post in the history, or waits for the next slot
+// if there is none.
+fn first_slot(schedule: &Schedule, history: &History, missed: Missed) -> Option<DateTime<Utc>> {
+    match history.entries().last() {
+        Some(last) => schedule.resume(last.posted_at, Utc::now(), missed),
+        None => schedule.next_slot(Utc::now()),
+    }
+}
+
+// `toot --dry-run` goes through everything a post does except sending it, and
+// prints each status exactly as it would be sent. A thread's replies point at
+// placeholders, since the real ids are only known once posted.
+fn dry_run() {
+    let settings = settings();
+    let gazetteer = gazetteer();
+    let history = history();
+    let toot = match random_location(&gazetteer, &settings, &history, Utc::now(), &mut seed::rng(settings.seed)) {
+        Ok(toot) => toot,
+        Err(e) => panic!("{}", e),
+    };
+
+    println!("Would post about {} ({})", toot.name, toot.city);
+    for (index, status) in toot.statuses.into_iter().enumerate() {
+        let reply_to = (index > 0).then(|| format!("<id of status {}>", index));
+        let payload = new_status(status, reply_to.as_deref(), &settings);
+        match serde_json::to_string_pretty(&payload) {
+            Ok(json) => println!("{}", json),
+            Err(e) => panic!("Could not show status: {}", e),
+        }
+    }
+}
+
+// `toot preview --count <n>` lists the next posts `toot daemon` would make
+// with the same settings, as if each went out on time. With `--daily` each
+// slot is seeded as the daemon will seed it; otherwise the cities are only a
+// sample of what might come up.
+fn preview() {
+    let count = match cli::flag_value(std::env::args().skip(2), "--count") {
+        Some(count) => count.parse().unwrap_or_else(|_| panic!("`--count` must be a number, not `{}`", count)),
+        None => 5,
+    };
+    let schedule = schedule();
+    let missed = schedule::missed_arg(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+    let settings = settings();
+    let gazetteer = gazetteer();
+    // Each previewed post counts towards the next, but is not written down.
+    let history = history().snapshot();
+    let mut rng = seed::rng(settings.seed);
+
+    let first = first_slot(&schedule, &history, missed);
+    for (number, (at, toot)) in upcoming(&gazetteer, &settings, &schedule, history, first, count, &mut rng).into_iter().enumerate() {
+        match toot {
+            Ok(toot) => {
+                println!("{}. {} - {}", number + 1, at.to_rfc3339(), toot.name);
+                for status in &toot.statuses {
+                    println!("{}", status);
+                }
+            }
+            Err(e) => println!("{}. {} - {}", number + 1, at.to_rfc3339(), e),
+        }
+        println!();
+    }
+}
+
+// The next `count` posts from the slot `first` on, each chosen as the daemon
+// would at its own time, so repeat windows open and close as they would then.
+fn upcoming(
+    gazetteer: &Gazetteer,
+    settings: &Settings,
+    schedule: &Schedule,
+    mut history: History,
+    first: Option<DateTime<Utc>>,
+    count: usize,
+    rng: &mut StdRng,
+) -> Vec<(DateTime<Utc>, Result<Toot, TootError>)> {
+    let mut posts = Vec::new();
+    let mut slot = first;
+    while let Some(due) = slot {
+        if posts.len() == count {
+            break;
+        }
+        seed_slot(settings, due, rng);
+        let at = due + schedule.jitter(rng);
+        let toot = random_location(gazetteer, settings, &history, at, rng);
+        if let Ok(toot) = &toot {
+            let entry = Entry { city: toot.city, name: toot.name.clone(), posted_at: at, status_id: None };
+            history.record(entry).unwrap_or_else(|e| panic!("{}", e));
+        }
+        posts.push((at, toot));
+        slot = schedule.next_slot(due);
+    }
+    posts
+}
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn request_stop(_: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+// A post already under way is finished before the daemon stops.
+#[cfg(unix)]
+fn stop_on_signals() {
+    let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: `request_stop` only stores to an atomic, which is
+    // async-signal-safe: it takes no lock and does not allocate, so it is
+    // sound whatever the interrupted thread was doing. It has the signature
+    // `signal` expects and, being a plain function, lives as long as the
+    // process.
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_on_signals() {}
+
+// Sleeps in short steps so a stop request is noticed promptly. Returns false
+// if asked to stop before `at`.
+fn sleep_until(at: DateTime<Utc>) -> bool {
+    loop {
+        if STOP.load(Ordering::SeqCst) {
+            return false;
+        }
+        let left = at - Utc::now();
+        if left <= chrono::Duration::zero() {
+            return true;
+        }
+        let step = left.to_std().unwrap_or_default().min(std::time::Duration::from_secs(1));
+        std::thread::sleep(step);
+    }
+}
+
+fn log(message: &str) {
+    println!("[{}] {}", Utc::now().to_rfc3339(), message);
+}
+
+// What is sent to `POST /api/v1/statuses`. elefren's own `NewStatus` can
+// only carry the language as an ISO 639-3 code such as `eng`, which Mastodon
+// does not accept, so the locale's two-letter code is sent as it is.
+#[derive(Debug, Serialize)]
+struct NewStatus {
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_reply_to_id: Option<String>,
+    language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<Visibility>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spoiler_text: Option<String>,
+}
+
+// Each status is tagged with the language of the locale it was written in,
+// and carries the configured visibility and content warning.
+fn new_status(status: String, reply_to: Option<&str>, settings: &Settings) -> NewStatus {
+    NewStatus {
+        status,
+        in_reply_to_id: reply_to.map(String::from),
+        language: settings.options.locale.code.clone(),
+        visibility: settings.visibility,
+        spoiler_text: settings.spoiler.clone(),
+    }
+}
+
+// Posts one status the way elefren's `new_status` does, but with our payload.
+fn post_status(mastodon: &Mastodon, new_status: &NewStatus) -> elefren::Result<Status> {
+    let url = format!("{}/api/v1/statuses", mastodon.data.base);
+    let mut response = reqwest::Client::new().post(&url).bearer_auth(&mastodon.data.token).json(new_status).send()?;
+    let status = response.status();
+    if status.is_client_error() {
+        return Err(elefren::Error::Client(status));
+    } else if status.is_server_error() {
+        return Err(elefren::Error::Server(status));
+    }
+    Ok(response.json()?)
+}
+
+// Posts each status as a reply to the one before it. If one fails, the ones
+// already posted are deleted again so no half-finished thread is left behind.
+// Returns the ids of the posted statuses.
+fn post_statuses(mastodon: &Mastodon, statuses: Vec<String>, settings: &Settings) -> elefren::Result<Vec<String>> {
+    let mut posted: Vec<String> = Vec::new();
+
+    for status in statuses {
+        let new_status = new_status(status, posted.last().map(String::as_str), settings);
+        println!("NewStatus = {:#?}", new_status);
+
+        match post_status(mastodon, &new_status) {
+            Ok(status) => {
+                println!("Status posted successfully");
+                posted.push(status.id);
+            }
+            Err(e) => {
+                for id in posted.iter().rev() {
+                    match mastodon.delete_status(id) {
+                        Ok(_) => println!("Deleted status {}", id),
+                        Err(e) => eprintln!("Could not delete status {}: {}", id, e),
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(posted)
+}
+
+// Why there is nothing to post for a slot.
+#[derive(Debug)]
+enum TootError {
+    NoCity,
+    Compose(ComposeError),
+}
+
+impl fmt::Display for TootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TootError::NoCity => write!(f, "No cities to choose from"),
+            TootError::Compose(e) => write!(f, "Could not compose status: {}", e),
+        }
+    }
+}
+
+impl From<ComposeError> for TootError {
+    fn from(e: ComposeError) -> TootError {
+        TootError::Compose(e)
+    }
+}
+
+// The city chosen for this run and the statuses about it.
+struct Toot {
+    city: CityId,
+    name: String,
+    statuses: Vec<String>,
+}
+
+// Everything about what to post that is read from the command line and
+// `.env`, checked once up front.
+struct Settings {
+    options: TextOptions,
+    // `--seed <n>`, `--daily` or `--date <date>` make the choice repeatable.
+    seed: Option<u64>,
+    // With a plain `--daily`, `toot daemon` reseeds each slot from its date.
+    daily: bool,
+    // How cities are chosen can be set in `.env`; see `Selection::from_args`.
+    selection: Selection,
+    // Cities the history says were posted too recently are skipped; see
+    // `history::repeats_arg`.
+    repeats: Repeats,
+    // With `--thread`, a text too long for one status is posted as a thread
+    // instead of being shortened.
+    thread: bool,
+    // Instances can raise the status length limit; set `STATUS_LIMIT` to
+    // match.
+    limit: usize,
+    // `--visibility public|unlisted|private|direct` or `TOOT_VISIBILITY`;
+    // otherwise the account's default.
+    visibility: Option<Visibility>,
+    // A content warning from `--spoiler <text>` or `TOOT_SPOILER`.
+    spoiler: Option<String>,
+}
+
+fn settings() -> Settings {
+    Settings {
+        options: cli::text_options(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e)),
+        seed: seed::seed_from_args(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e)),
+        daily: seed::daily_arg(std::env::args().skip(1)),
+        selection: Selection::from_args(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e)),
+        repeats: history::repeats_arg(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e)),
+        thread: cli::has_flag(std::env::args(), "--thread"),
+        limit: status_limit(),
+        visibility: cli::setting(std::env::args().skip(1), "--visibility", "TOOT_VISIBILITY").map(|visibility| match visibility.as_str() {
+            "public" => Visibility::Public,
+            "unlisted" => Visibility::Unlisted,
+            "private" => Visibility::Private,
+            "direct" => Visibility::Direct,
+            _ => panic!("`{}` is not a visibility, expected `public`, `unlisted`, `private` or `direct`", visibility),
+        }),
+        spoiler: cli::setting(std::env::args().skip(1), "--spoiler", "TOOT_SPOILER"),
+    }
+}
+
+fn gazetteer() -> Gazetteer {
+    let data_path = data_loader::data_path_arg(std::env::args().skip(1));
+    match Gazetteer::load_configured(data_path.as_deref()) {
+        Ok(gazetteer) => gazetteer,
+        Err(e) => panic!("Could not load city data: {}", e),
+    }
+}
+
+// Chooses and writes up a city as if posting at `now`, which decides what the
+// history still rules out.
+fn random_location<R: Rng + ?Sized>(
+    gazetteer: &Gazetteer,
+    settings: &Settings,
+    history: &History,
+    now: DateTime<Utc>,
+    rng: &mut R,
+) -> Result<Toot, TootError> {
+    let city = match gazetteer.select_fresh_city(&settings.selection, history, settings.repeats, now, rng) {
+        Some(city) => city,
+        None => return Err(TootError::NoCity),
+    };
+    let statuses = if settings.thread {
+        gazetteer.compose_thread(city, &settings.options, settings.limit)?
+    } else {
+        vec![gazetteer.compose_location(city, &settings.options, settings.limit)?]
+    };
+    Ok(Toot { city: city.id(), name: city.name().to_string(), statuses })
+}
+
+// Posted cities are remembered in `--history <path>` or `TOOT_HISTORY`; without
+// either, nothing is remembered between runs.
+fn history() -> History {
+    match history::history_path(std::env::args().skip(1)) {
+        Some(path) => History::open(path).unwrap_or_else(|e| panic!("{}", e)),
+        None => History::new(),
+    }
+}
+
+// The statuses are already up, so failing to remember them is only a warning.
+fn record(history: &mut History, city: CityId, name: String, posted: Vec<String>) {
+    let entry = Entry { city, name, posted_at: Utc::now(), status_id: posted.into_iter().next() };
+    if let Err(e) = history.record(entry) {
+        eprintln!("{}", e);
+    }
+}
+
+fn status_limit() -> usize {
+    match ::std::env::var("STATUS_LIMIT") {
+        Ok(limit) => limit.parse().unwrap_or_else(|e| panic!("`STATUS_LIMIT` must be a number: {}", e)),
+        Err(_) => compose::DEFAULT_STATUS_LIMIT,
+    }
 }
 
 fn env(s: &str) -> String {
@@ -99,3 +523,91 @@ fn env(s: &str) -> String {
     		panic!("must have `{}` definded", s)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use mastodon_toot_bot::schedule::Timing;
+    use mastodon_toot_bot::City;
+    use mastodon_toot_bot::locale::Locale;
+
+    #[test]
+    fn it_previews_what_the_history_allows_at_each_slot() {
+        let (first, second) = (City::new("First", 10.0, 10.0, 100.0, "Testland", "").unwrap(), City::new("Second", -10.0, -10.0, 100.0, "Testland", "").unwrap());
+        let gazetteer: Gazetteer = vec![first.clone(), second.clone()].into_iter().collect();
+        let settings = Settings {
+            options: TextOptions::default(),
+            seed: None,
+            daily: false,
+            selection: Selection::default(),
+            repeats: Repeats::After(Duration::hours(36)),
+            thread: false,
+            limit: compose::DEFAULT_STATUS_LIMIT,
+            visibility: None,
+            spoiler: None,
+        };
+        let schedule = Schedule { timing: Timing::Every(Duration::days(1)), jitter: Duration::zero() };
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut history = History::new();
+        history.record(Entry { city: first.id(), name: first.name().to_string(), posted_at: start, status_id: None }).unwrap();
+
+        let posts = upcoming(&gazetteer, &settings, &schedule, history, schedule.next_slot(start), 2, &mut seed::rng(Some(1)));
+
+        // A day on, First is still inside its window; two days on it is out
+        // of it while Second, posted a day earlier, is not.
+        let names: Vec<_> = posts.iter().map(|(_, toot)| toot.as_ref().unwrap().name.as_str()).collect();
+        assert_eq!(names, vec!["Second", "First"]);
+        assert_eq!(posts[1].0, start + Duration::days(2));
+    }
+
+    #[test]
+    fn it_tags_statuses_with_the_two_letter_language_code() {
+        let settings = Settings {
+            options: TextOptions { locale: Locale::german(), ..TextOptions::default() },
+            seed: None,
+            daily: false,
+            selection: Selection::default(),
+            repeats: Repeats::Allow,
+            thread: false,
+            limit: compose::DEFAULT_STATUS_LIMIT,
+            visibility: Some(Visibility::Unlisted),
+            spoiler: None,
+        };
+
+        let json = serde_json::to_string(&new_status(String::from("Hallo"), Some("1"), &settings)).unwrap();
+
+        assert!(json.contains(r#""language":"de""#), "{}", json);
+        assert!(json.contains(r#""in_reply_to_id":"1""#), "{}", json);
+        assert!(json.contains(r#""visibility":"unlisted""#), "{}", json);
+    }
+
+    #[test]
+    fn it_previews_each_day_of_a_daily_seed_the_same_from_any_start() {
+        let gazetteer: Gazetteer = (0..20)
+            .map(|n| City::new(format!("City {}", n), f64::from(n), f64::from(n), 100.0, String::from("Testland"), String::new()).unwrap())
+            .collect();
+        let settings = Settings {
+            options: TextOptions::default(),
+            seed: None,
+            daily: true,
+            selection: Selection::default(),
+            repeats: Repeats::Allow,
+            thread: false,
+            limit: compose::DEFAULT_STATUS_LIMIT,
+            visibility: None,
+            spoiler: None,
+        };
+        let schedule = Schedule { timing: Timing::Every(Duration::days(1)), jitter: Duration::hours(1) };
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let names = |first, count| -> Vec<String> {
+            upcoming(&gazetteer, &settings, &schedule, History::new(), Some(first), count, &mut seed::rng(Some(1)))
+                .into_iter()
+                .map(|(at, toot)| format!("{} {}", at, toot.unwrap().name))
+                .collect()
+        };
+
+        // A day's post, jitter included, does not depend on the days before it.
+        assert_eq!(names(start, 3)[1..], names(start + Duration::days(1), 2)[..]);
+    }
+}
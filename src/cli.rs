@@ -0,0 +1,114 @@
+// Helpers for the command line flags shared by the binaries.
+
+use std::env;
+
+use super::locale::Locale;
+use super::template::{self, Phrasebook, Template, TemplateError};
+use super::{NameStyle, TextOptions};
+
+/// Finds the value of `flag <value>` (or `flag=<value>`) in command line
+/// arguments.
+pub fn flag_value<I: IntoIterator<Item = String>>(args: I, flag: &str) -> Option<String> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(String::from(value));
+        }
+    }
+    None
+}
+
+pub fn has_flag<I: IntoIterator<Item = String>>(args: I, flag: &str) -> bool {
+    args.into_iter().any(|arg| arg == flag)
+}
+
+/// The value of `flag`, or else of the environment variable `var`.
+pub fn setting<I: IntoIterator<Item = String>>(args: I, flag: &str, var: &str) -> Option<String> {
+    flag_value(args, flag).or_else(|| env::var(var).ok())
+}
+
+/// The language from `--locale <code or path>` or `TOOT_LOCALE`, with custom
+/// wording from `--templates <path>` or `TOOT_TEMPLATES` on top of it.
+pub fn locale_arg<I: IntoIterator<Item = String>>(args: I) -> Result<Locale, TemplateError> {
+    let args: Vec<String> = args.into_iter().collect();
+
+    let mut locale = match setting(args.clone(), "--locale", "TOOT_LOCALE") {
+        Some(code) => Locale::load(&code)?,
+        None => Locale::english(),
+    };
+    if let Some(path) = setting(args, "--templates", "TOOT_TEMPLATES") {
+        locale.phrasebook = Phrasebook::load_from_path(path, &locale.phrasebook)?;
+    }
+    Ok(locale)
+}
+
+/// Hashtags from `--hashtags "Geography {city}"` or `TOOT_HASHTAGS`.
+pub fn hashtags_arg<I: IntoIterator<Item = String>>(args: I) -> Result<Vec<Template>, TemplateError> {
+    match setting(args, "--hashtags", "TOOT_HASHTAGS") {
+        Some(spec) => template::hashtags(&spec),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// How the text is written, from `--antipode`, `--flags`, `--ascii` and the
+/// locale and hashtag settings above.
+pub fn text_options<I: IntoIterator<Item = String>>(args: I) -> Result<TextOptions, TemplateError> {
+    let args: Vec<String> = args.into_iter().collect();
+
+    Ok(TextOptions {
+        antipode: has_flag(args.clone(), "--antipode"),
+        locale: locale_arg(args.clone())?,
+        flags: has_flag(args.clone(), "--flags"),
+        hashtags: hashtags_arg(args.clone())?,
+        names: if has_flag(args, "--ascii") { NameStyle::Ascii } else { NameStyle::Native },
+        ..TextOptions::default()
+    })
+}
+
+/// Command line arguments from string literals, for the tests of every
+/// module that reads flags.
+#[cfg(test)]
+pub(crate) fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_flag_values() {
+        assert_eq!(flag_value(args(&["--templates", "pirate.txt"]), "--templates"), Some(String::from("pirate.txt")));
+        assert_eq!(flag_value(args(&["--templates=pirate.txt"]), "--templates"), Some(String::from("pirate.txt")));
+        assert_eq!(flag_value(args(&["--templates"]), "--templates"), None);
+        assert_eq!(flag_value(args(&["--templatesx"]), "--templates"), None);
+    }
+
+    #[test]
+    fn it_finds_flags() {
+        assert!(has_flag(args(&["--thread", "--antipode"]), "--antipode"));
+        assert!(!has_flag(args(&["--thread"]), "--antipode"));
+    }
+
+    #[test]
+    fn it_reads_the_locale_and_hashtags() {
+        assert_eq!(locale_arg(args(&["toot", "--locale", "de"])).unwrap().code, "de");
+        assert!(locale_arg(args(&["toot", "--locale", "/no/such/locale.txt"])).is_err());
+        assert_eq!(hashtags_arg(args(&["toot", "--hashtags", "Geography {city}"])).unwrap().len(), 2);
+        assert!(hashtags_arg(args(&["toot", "--hashtags", "{nope}"])).is_err());
+    }
+
+    #[test]
+    fn it_reads_the_text_options() {
+        let options = text_options(args(&["toot", "--flags", "--ascii", "--locale", "fr"])).unwrap();
+
+        assert!(options.flags);
+        assert!(!options.antipode);
+        assert_eq!(options.names, NameStyle::Ascii);
+        assert_eq!(options.locale.code, "fr");
+        assert!(text_options(args(&["toot", "--hashtags", "{nope}"])).is_err());
+    }
+}
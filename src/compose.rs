@@ -0,0 +1,65 @@
+// Fitting location texts into a Mastodon status.
+
+use std::{error, fmt};
+
+/// The status length limit of a stock Mastodon instance.
+pub const DEFAULT_STATUS_LIMIT: usize = 500;
+
+/// Every link counts as this many characters, however long it really is.
+pub const URL_LENGTH: usize = 23;
+
+/// The length of a status as Mastodon counts it: characters rather than bytes,
+/// every link as `URL_LENGTH`, and remote mentions by the username alone.
+pub fn status_length(text: &str) -> usize {
+    let mut length = text.chars().count();
+
+    for word in text.split_whitespace() {
+        if word.starts_with("http://") || word.starts_with("https://") {
+            // Punctuation closing a sentence isn't part of the link.
+            let url = word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+            length = length - url.chars().count() + URL_LENGTH;
+        } else if let Some(domain) = word.strip_prefix('@').and_then(|rest| rest.find('@').map(|at| &rest[at..])) {
+            length -= domain.chars().count();
+        }
+    }
+
+    length
+}
+
+/// The text could not be made short enough without cutting into names.
+#[derive(Debug, PartialEq)]
+pub struct ComposeError {
+    pub budget: usize,
+    pub shortest: usize,
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Status needs at least {} characters but only {} are allowed", self.shortest, self.budget)
+    }
+}
+
+impl error::Error for ComposeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_characters_not_bytes() {
+        assert_eq!(status_length("Darién"), 6);
+        assert_eq!(status_length("40°25'N"), 7);
+    }
+
+    #[test]
+    fn it_counts_links_as_a_fixed_length() {
+        assert_eq!(status_length("see https://example.com/a/very/long/path/indeed"), 4 + URL_LENGTH);
+        assert_eq!(status_length("see http://x.io."), 4 + URL_LENGTH + 1);
+    }
+
+    #[test]
+    fn it_counts_remote_mentions_by_username() {
+        assert_eq!(status_length("hi @honor@botsin.space"), 9);
+        assert_eq!(status_length("hi @honor"), 9);
+    }
+}
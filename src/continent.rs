@@ -0,0 +1,139 @@
+// Which continent a city is on, worked out from its country code since the
+// dataset has no continent column, and the corrections that code needs.
+
+use serde::Serialize;
+use std::fmt;
+
+use super::City;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Continent {
+    Africa,
+    Antarctica,
+    Asia,
+    Europe,
+    NorthAmerica,
+    Oceania,
+    SouthAmerica,
+}
+
+impl fmt::Display for Continent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Continent::Africa => "Africa",
+            Continent::Antarctica => "Antarctica",
+            Continent::Asia => "Asia",
+            Continent::Europe => "Europe",
+            Continent::NorthAmerica => "North America",
+            Continent::Oceania => "Oceania",
+            Continent::SouthAmerica => "South America",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const AFRICA: &[&str] = &[
+    "AO", "BF", "BI", "BJ", "BW", "CD", "CF", "CG", "CI", "CM", "CV", "DJ", "DZ", "EG", "EH", "ER",
+    "ET", "GA", "GH", "GM", "GN", "GQ", "GW", "KE", "KM", "LR", "LS", "LY", "MA", "MG", "ML", "MR",
+    "MU", "MW", "MZ", "NA", "NE", "NG", "RE", "RW", "SC", "SD", "SL", "SN", "SO", "SS", "ST", "SZ",
+    "TD", "TG", "TN", "TZ", "UG", "YT", "ZA", "ZM", "ZW",
+];
+const ASIA: &[&str] = &[
+    "AE", "AF", "AM", "AZ", "BD", "BH", "BN", "BT", "CN", "CY", "GE", "HK", "ID", "IL", "IN", "IQ",
+    "IR", "JO", "JP", "KG", "KH", "KP", "KR", "KW", "KZ", "LA", "LB", "LK", "MM", "MN", "MO", "MV",
+    "MY", "NP", "OM", "PH", "PK", "PS", "QA", "SA", "SG", "SY", "TH", "TJ", "TL", "TM", "TR", "TW",
+    "UZ", "VN", "YE",
+];
+const EUROPE: &[&str] = &[
+    "AD", "AL", "AT", "AX", "BA", "BE", "BG", "BY", "CH", "CZ", "DE", "DK", "EE", "ES", "FI", "FO",
+    "FR", "GB", "GI", "GR", "HR", "HU", "IE", "IM", "IS", "IT", "LI", "LT", "LU", "LV", "MC", "MD",
+    "ME", "MK", "MT", "NL", "NO", "PL", "PT", "RO", "RS", "RU", "SE", "SI", "SJ", "SK", "SM", "UA",
+    "VA", "XK",
+];
+const NORTH_AMERICA: &[&str] = &[
+    "AG", "AW", "BB", "BM", "BS", "BZ", "CA", "CR", "CU", "CW", "DM", "DO", "GD", "GL", "GP", "GT",
+    "HN", "HT", "JM", "KN", "KY", "LC", "MQ", "MX", "NI", "PA", "PR", "SV", "TC", "TT", "US", "VC",
+    "VI",
+];
+const SOUTH_AMERICA: &[&str] = &[
+    "AR", "BO", "BR", "CL", "CO", "EC", "FK", "GF", "GS", "GY", "PE", "PY", "SR", "UY", "VE",
+];
+const OCEANIA: &[&str] = &[
+    "AS", "AU", "CK", "FJ", "FM", "GU", "KI", "MH", "MP", "NC", "NZ", "PF", "PG", "PW", "SB", "TO",
+    "TV", "VU", "WS",
+];
+
+/// The ISO 3166-1 alpha-2 code of a country, going by its alpha-3 code where
+/// the dataset's is wrong: Senegal is listed as `SM`, San Marino's, and
+/// Kosovo, Somaliland and Northern Cyprus have no code of their own.
+pub fn iso2_of<'a>(iso2: &'a str, iso3: &str) -> Option<&'a str> {
+    match iso3 {
+        "SEN" => Some("SN"),
+        "KOS" | "SOL" | "CYN" => None,
+        _ => Some(iso2),
+    }
+}
+
+/// The continent of a country, by its ISO codes.
+///
+/// Places without an alpha-2 code are found by their alpha-3 code instead.
+pub fn of_country(iso2: &str, iso3: &str) -> Option<Continent> {
+    match iso3 {
+        "SOL" => return Some(Continent::Africa),
+        "KOS" => return Some(Continent::Europe),
+        "CYN" => return Some(Continent::Asia),
+        _ => {}
+    }
+    let iso2 = iso2_of(iso2, iso3)?;
+
+    let continents = [
+        (AFRICA, Continent::Africa),
+        (ASIA, Continent::Asia),
+        (EUROPE, Continent::Europe),
+        (NORTH_AMERICA, Continent::NorthAmerica),
+        (SOUTH_AMERICA, Continent::SouthAmerica),
+        (OCEANIA, Continent::Oceania),
+        (&["AQ"], Continent::Antarctica),
+    ];
+    continents
+        .iter()
+        .find(|(codes, _)| codes.contains(&iso2))
+        .map(|&(_, continent)| continent)
+}
+
+impl City {
+    pub fn continent(&self) -> Option<Continent> {
+        of_country(&self.iso2, &self.iso3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_continents() {
+        assert_eq!(of_country("US", "USA"), Some(Continent::NorthAmerica));
+        assert_eq!(of_country("GF", "FRA"), Some(Continent::SouthAmerica));
+        assert_eq!(of_country("SM", "SEN"), Some(Continent::Africa));
+        assert_eq!(of_country("SM", "SMR"), Some(Continent::Europe));
+        assert_eq!(of_country("", ""), None);
+    }
+
+    #[test]
+    fn it_corrects_country_codes() {
+        assert_eq!(iso2_of("SM", "SEN"), Some("SN"));
+        assert_eq!(iso2_of("SM", "SMR"), Some("SM"));
+        assert_eq!(iso2_of("-99", "KOS"), None);
+        assert_eq!(iso2_of("", "CYN"), None);
+    }
+
+    #[test]
+    fn it_places_every_country_in_the_dataset() {
+        let cities = crate::data_loader::load_from_path(crate::data_loader::DEFAULT_DATA_PATH).unwrap();
+
+        let unplaced: Vec<_> = cities.iter().filter(|city| city.continent().is_none()).map(|city| city.country()).collect();
+
+        assert!(unplaced.is_empty(), "no continent for {:?}", unplaced);
+    }
+}
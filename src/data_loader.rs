@@ -1,73 +1,364 @@
 use serde::Deserialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use csv::ReaderBuilder;
 use std::fs::File;
-use std::convert::From;
+use std::io::Read;
+use std::convert::TryFrom;
+use std::collections::HashMap;
+use std::{env, error, fmt, io};
 
-use super::City;
+use super::{cli, geo, search, City, CityError};
+
+/// Environment variable naming the CSV file to load cities from.
+pub const DATA_PATH_ENV: &str = "TOOT_DATA_PATH";
+
+/// Command line flag naming the CSV file to load cities from.
+pub const DATA_PATH_FLAG: &str = "--data";
+
+/// File loaded when neither the flag nor the environment variable is set and
+/// the `embedded-data` feature is disabled.
+pub const DEFAULT_DATA_PATH: &str = "./simplemaps-worldcities-basic.csv";
 
 #[derive(Deserialize)]
 struct Record {
     city: String,
-    _city_ascii: String,
+    city_ascii: String,
     latitude: f64,
     longitude: f64,
     population: f64,
     country: String,
-    _iso2: String,
-    _iso3: String,
+    iso2: String,
+    iso3: String,
     province: String,
 }
 
-impl From<Record> for City {
-    fn from(record: Record) -> City {
-        City {
-            name: record.city,
-            latitude: record.latitude,
-            longitude: record.longitude,
-            population: record.population,
-            country: record.country,
-            province: record.province,
+impl TryFrom<Record> for City {
+    type Error = CityError;
+
+    fn try_from(record: Record) -> Result<City, CityError> {
+        let population = unknown_population_as_zero(record.population);
+        Ok(City::new(record.city, record.latitude, record.longitude, population, record.country, record.province)?
+            .with_city_ascii(record.city_ascii)
+            .with_iso_codes(record.iso2, record.iso3))
+    }
+}
+
+/// The dataset marks a few unknown populations as -99; they load as 0.
+pub(crate) fn unknown_population_as_zero(population: f64) -> f64 {
+    if population < 0.0 { 0.0 } else { population }
+}
+
+/// Rows with the same name in the same province and country closer together
+/// than this are reported as near duplicates.
+pub const NEAR_DUPLICATE_KM: f64 = 10.0;
+
+/// A row that repeats an earlier one.
+#[derive(Debug, PartialEq)]
+pub struct Duplicate {
+    /// Rows are counted from 1, like in `LoadError`.
+    pub row: usize,
+    pub first_row: usize,
+    pub name: String,
+    pub distance_km: f64,
+}
+
+/// The duplicates found while loading.
+#[derive(Debug, Default, PartialEq)]
+pub struct DuplicateReport {
+    /// Rows with the same id as an earlier row. Only the first is kept, so
+    /// every loaded city has its own id.
+    pub exact: Vec<Duplicate>,
+    /// Rows with the same name in the same province and country within
+    /// `NEAR_DUPLICATE_KM` of an earlier row. Both are kept, as they may be
+    /// different places, but one may be a mistake.
+    pub near: Vec<Duplicate>,
+}
+
+impl DuplicateReport {
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.near.is_empty()
+    }
+}
+
+impl fmt::Display for DuplicateReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} duplicate and {} near-duplicate rows", self.exact.len(), self.near.len())?;
+        for duplicate in &self.exact {
+            write!(f, "\n  row {} repeats row {} ({}), dropped", duplicate.row, duplicate.first_row, duplicate.name)?;
+        }
+        for duplicate in &self.near {
+            write!(f, "\n  row {} is {:.1} km from row {} ({})", duplicate.row, duplicate.distance_km, duplicate.first_row, duplicate.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Drops rows repeating an earlier row's id and reports them, together with
+/// rows that are suspiciously close to an earlier row of the same name.
+pub fn deduplicate(cities: Vec<City>) -> (Vec<City>, DuplicateReport) {
+    let mut report = DuplicateReport::default();
+    let mut first_rows = HashMap::new();
+    // Rows kept so far for each name, province and country, with their
+    // positions in `kept`, to look for near duplicates.
+    let mut namesakes: HashMap<(String, String, String), Vec<(usize, usize)>> = HashMap::new();
+    let mut kept: Vec<City> = Vec::with_capacity(cities.len());
+
+    for (index, city) in cities.into_iter().enumerate() {
+        let row = index + 1;
+        if let Some(&first_row) = first_rows.get(&city.id()) {
+            report.exact.push(Duplicate { row, first_row, name: city.name.clone(), distance_km: 0.0 });
+            continue;
+        }
+        first_rows.insert(city.id(), row);
+
+        let rows = namesakes.entry((search::fold(&city.name), city.province.clone(), city.country.clone())).or_default();
+        for &(first_row, position) in rows.iter() {
+            let first = &kept[position];
+            let distance_km = geo::haversine_km(first.latitude, first.longitude, city.latitude, city.longitude);
+            if distance_km <= NEAR_DUPLICATE_KM {
+                report.near.push(Duplicate { row, first_row, name: city.name.clone(), distance_km });
+                break;
+            }
+        }
+        rows.push((row, kept.len()));
+        kept.push(city);
+    }
+
+    (kept, report)
+}
+
+/// Everything that can go wrong while loading city data.
+///
+/// `source` is the file path, or `<reader>` when loading from a stream.
+#[derive(Debug)]
+pub enum LoadError {
+    Open { source: String, error: io::Error },
+    Row { source: String, row: usize, error: csv::Error },
+    Invalid { source: String, row: usize, error: CityError },
+    Empty { source: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Open { source, error } =>
+                write!(f, "Failed to open file {}: {}", source, error),
+            LoadError::Row { source, row, error } =>
+                write!(f, "Invalid city data in {} at row {}: {}", source, row, error),
+            LoadError::Invalid { source, row, error } =>
+                write!(f, "Invalid city in {} at row {}: {}", source, row, error),
+            LoadError::Empty { source } =>
+                write!(f, "No valid city data found in {}", source),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LoadError::Open { error, .. } => Some(error),
+            LoadError::Row { error, .. } => Some(error),
+            LoadError::Invalid { error, .. } => Some(error),
+            LoadError::Empty { .. } => None,
         }
     }
 }
 
-pub fn load_data() -> Result<Vec<City>, Box<dyn std::error::Error>> {
-    let file_path = Path::new("./simplemaps-worldcities-basic.csv");
+/// Picks the external data file: an explicit path wins, then `TOOT_DATA_PATH`.
+pub fn configured_path(explicit: Option<&Path>) -> Option<PathBuf> {
+    explicit
+        .map(Path::to_path_buf)
+        .or_else(|| env::var_os(DATA_PATH_ENV).map(PathBuf::from))
+}
+
+/// Finds the value of `--data <path>` (or `--data=<path>`) in command line
+/// arguments.
+pub fn data_path_arg<I: IntoIterator<Item = String>>(args: I) -> Option<PathBuf> {
+    cli::flag_value(args, DATA_PATH_FLAG).map(PathBuf::from)
+}
+
+/// Loads cities from `TOOT_DATA_PATH`, falling back to the default dataset.
+pub fn load_data() -> Result<Vec<City>, LoadError> {
+    load_configured(None)
+}
+
+/// Loads cities from the configured file (see `configured_path`), falling back
+/// to the default dataset when none is configured.
+pub fn load_configured(explicit: Option<&Path>) -> Result<Vec<City>, LoadError> {
+    match configured_path(explicit) {
+        Some(path) => load_from_path(path),
+        None => load_default(),
+    }
+}
+
+#[cfg(feature = "embedded-data")]
+fn load_default() -> Result<Vec<City>, LoadError> {
+    Ok(deduplicate(super::embedded_data::cities()).0)
+}
+
+#[cfg(not(feature = "embedded-data"))]
+fn load_default() -> Result<Vec<City>, LoadError> {
+    load_from_path(DEFAULT_DATA_PATH)
+}
+
+pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Vec<City>, LoadError> {
+    load_from_path_with_report(path).map(|(cities, _)| cities)
+}
+
+/// Like `load_from_path`, also returning the duplicates that were found.
+pub fn load_from_path_with_report<P: AsRef<Path>>(path: P) -> Result<(Vec<City>, DuplicateReport), LoadError> {
+    let file_path = path.as_ref();
+    let source = file_path.display().to_string();
     let file = File::open(file_path)
-        .map_err(|e| format!("Failed to open file {}: {}", file_path.display(), e))?;
+        .map_err(|error| LoadError::Open { source: source.clone(), error })?;
 
-    // Initialize CSV reader
-    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(file);
+    eprintln!("=== Reading data from file: {:?} ===", file_path);
 
-    // Print the number of records to be processed
-    println!("=== Reading data from file: {:?} ===", file_path);
+    read_cities(file, source)
+}
+
+pub fn load_from_reader<R: Read>(reader: R) -> Result<Vec<City>, LoadError> {
+    read_cities(reader, String::from("<reader>")).map(|(cities, _)| cities)
+}
+
+fn read_cities<R: Read>(reader: R, source: String) -> Result<(Vec<City>, DuplicateReport), LoadError> {
+    // Initialize CSV reader
+    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(reader);
 
     let mut cities: Vec<City> = Vec::new();
 
-    // Deserialize the CSV rows
+    // Deserialize the CSV rows, reporting rows counted from 1 like a text editor would
     for (index, result) in rdr.deserialize::<Record>().enumerate() {
-        match result {
-            Ok(record) => {
-                // Successfully deserialized, convert into City and push to the vector
-                let city: City = record.into();
-                cities.push(city);
-                if index % 1000 == 0 {
-                    println!("Processed {} records...", index + 1); // Print progress
-                }
-            }
-            Err(e) => {
-                // Error during deserialization, print which record failed
-                eprintln!("Error deserializing row {}: {:?}", index, e);
-            }
+        let record = result.map_err(|error| LoadError::Row {
+            source: source.clone(),
+            row: index + 1,
+            error,
+        })?;
+        let city = City::try_from(record).map_err(|error| LoadError::Invalid {
+            source: source.clone(),
+            row: index + 1,
+            error,
+        })?;
+        cities.push(city);
+        if index % 1000 == 0 {
+            eprintln!("Processed {} records...", index + 1); // Print progress
         }
     }
 
     // Check if we have data
     if cities.is_empty() {
-        return Err("No valid city data found in CSV file.".into());
+        return Err(LoadError::Empty { source });
     }
 
-    println!(" === Successfully loaded {} cities ===", cities.len());
-    Ok(cities)
-}
\ No newline at end of file
+    let (cities, report) = deduplicate(cities);
+    if !report.is_empty() {
+        eprintln!("{}: {}", source, report);
+    }
+
+    eprintln!(" === Successfully loaded {} cities ===", cities.len());
+    Ok((cities, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args;
+
+    #[test]
+    fn it_loads_cities_from_a_reader() {
+        let csv = "Pittsburgh,Pittsburgh,40.4299986,-79.99998539,1535267.5,United States of America,US,USA,Pennsylvania\n";
+
+        let cities = load_from_reader(csv.as_bytes()).unwrap();
+
+        assert_eq!(cities.len(), 1);
+        assert_eq!(cities[0].name, "Pittsburgh");
+        assert_eq!(cities[0].province, "Pennsylvania");
+        assert_eq!(cities[0].iso2(), "US");
+        assert_eq!(cities[0].iso3(), "USA");
+    }
+
+    #[test]
+    fn it_names_the_file_and_row_of_bad_data() {
+        let csv = "Pittsburgh,Pittsburgh,40.4299986,-79.99998539,1535267.5,United States of America,US,USA,Pennsylvania\n\
+                   Nowhere,Nowhere,not-a-number,0,0,Nowhere,NW,NWH,Nowhere\n";
+
+        let error = read_cities(csv.as_bytes(), String::from("cities.csv")).unwrap_err();
+
+        assert!(matches!(error, LoadError::Row { row: 2, .. }));
+        assert!(error.to_string().starts_with("Invalid city data in cities.csv at row 2"));
+    }
+
+    #[test]
+    fn it_rejects_impossible_coordinates() {
+        let csv = "Nowhere,Nowhere,95.0,0,0,Nowhere,NW,NWH,Nowhere\n";
+
+        let error = read_cities(csv.as_bytes(), String::from("cities.csv")).unwrap_err();
+
+        assert!(matches!(error, LoadError::Invalid { row: 1, error: CityError::Latitude(_), .. }));
+        assert_eq!(error.to_string(), "Invalid city in cities.csv at row 1: latitude 95 is outside [-90, 90]");
+    }
+
+    #[test]
+    fn it_loads_unknown_populations_as_zero() {
+        let csv = "Kavache,Kavache,70.73329104,136.2166361,-99,Russia,RU,RUS,Sakha (Yakutia)\n";
+
+        let cities = load_from_reader(csv.as_bytes()).unwrap();
+
+        assert_eq!(cities[0].population(), 0.0);
+    }
+
+    #[test]
+    fn it_drops_exact_duplicates() {
+        let csv = "Pittsburgh,Pittsburgh,40.4299986,-79.99998539,1535267.5,United States of America,US,USA,Pennsylvania\n\
+                   Erie,Erie,42.13,-80.085,135000,United States of America,US,USA,Pennsylvania\n\
+                   Pittsburgh,Pittsburgh,40.4299986,-79.99998539,1535267.5,United States of America,US,USA,Pennsylvania\n";
+
+        let (cities, report) = read_cities(csv.as_bytes(), String::from("cities.csv")).unwrap();
+
+        assert_eq!(cities.len(), 2);
+        assert_eq!(report.exact, vec![Duplicate { row: 3, first_row: 1, name: String::from("Pittsburgh"), distance_km: 0.0 }]);
+        assert!(report.near.is_empty());
+    }
+
+    #[test]
+    fn it_reports_near_duplicates() {
+        let csv = "Bandar Lampung,Tanjungkarang-Telubketung,-5.449604066,105.3000219,881801,Indonesia,ID,IDN,Lampung\n\
+                   Kansas City,Kansas City,39.1,-94.6,1000,United States of America,US,USA,Missouri\n\
+                   Kansas City,Kansas City,39.11,-94.63,1000,United States of America,US,USA,Kansas\n\
+                   Bandar Lampung,Bandar Lampung,-5.430018698,105.2699979,795757,Indonesia,ID,IDN,Lampung\n";
+
+        let (cities, report) = read_cities(csv.as_bytes(), String::from("cities.csv")).unwrap();
+
+        assert_eq!(cities.len(), 4);
+        assert!(report.exact.is_empty());
+        assert_eq!(report.near.len(), 1);
+        assert_eq!((report.near[0].row, report.near[0].first_row), (4, 1));
+        assert!(report.to_string().starts_with("0 duplicate and 1 near-duplicate rows\n  row 4 is 4.0 km from row 1 (Bandar Lampung)"));
+    }
+
+    #[test]
+    fn it_names_a_missing_file() {
+        let error = load_from_path("/does/not/exist.csv").unwrap_err();
+
+        assert!(error.to_string().starts_with("Failed to open file /does/not/exist.csv"));
+    }
+
+    #[test]
+    fn it_rejects_empty_data() {
+        let error = load_from_reader("".as_bytes()).unwrap_err();
+
+        assert!(matches!(error, LoadError::Empty { .. }));
+    }
+
+    #[test]
+    fn it_reads_the_data_flag() {
+        assert_eq!(data_path_arg(args(&["toot", "--data", "/var/lib/cities.csv"])), Some(PathBuf::from("/var/lib/cities.csv")));
+        assert_eq!(data_path_arg(args(&["toot", "--data=/var/lib/cities.csv"])), Some(PathBuf::from("/var/lib/cities.csv")));
+        assert_eq!(data_path_arg(args(&["toot"])), None);
+    }
+
+    #[test]
+    fn it_prefers_an_explicit_path() {
+        assert_eq!(configured_path(Some(Path::new("/var/lib/cities.csv"))), Some(PathBuf::from("/var/lib/cities.csv")));
+    }
+}
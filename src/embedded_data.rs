@@ -0,0 +1,51 @@
+// The world cities dataset, pre-parsed by `build.rs` into a static table so
+// loading it costs no CSV parsing at startup.
+
+use super::{data_loader, City};
+
+pub(crate) struct EmbeddedRecord {
+    city: &'static str,
+    city_ascii: &'static str,
+    latitude: f64,
+    longitude: f64,
+    population: f64,
+    country: &'static str,
+    iso2: &'static str,
+    iso3: &'static str,
+    province: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/embedded_cities.rs"));
+
+impl From<&EmbeddedRecord> for City {
+    fn from(record: &EmbeddedRecord) -> City {
+        City {
+            name: String::from(record.city),
+            city_ascii: String::from(record.city_ascii),
+            latitude: record.latitude,
+            longitude: record.longitude,
+            population: data_loader::unknown_population_as_zero(record.population),
+            country: String::from(record.country),
+            iso2: String::from(record.iso2),
+            iso3: String::from(record.iso3),
+            province: String::from(record.province),
+        }
+    }
+}
+
+pub(crate) fn cities() -> Vec<City> {
+    RECORDS.iter().map(City::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_embeds_the_same_cities_as_the_csv() {
+        let from_csv = data_loader::load_from_path(data_loader::DEFAULT_DATA_PATH).unwrap();
+
+        // Cities compare by id, so compare every field through their debug form.
+        assert_eq!(format!("{:?}", cities()), format!("{:?}", from_csv));
+    }
+}
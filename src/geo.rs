@@ -0,0 +1,128 @@
+// Great-circle geometry on a spherical Earth.
+
+/// Mean radius of the Earth.
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Length of one degree of latitude (and of longitude at the equator).
+pub const KM_PER_DEGREE: f64 = EARTH_RADIUS_KM * std::f64::consts::PI / 180.0;
+
+/// Half the Earth's circumference; no two points are further apart than this.
+pub const MAX_DISTANCE_KM: f64 = EARTH_RADIUS_KM * std::f64::consts::PI;
+
+/// Great-circle distance between two points given in decimal degrees.
+pub fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// The great circle through a point, heading off on an initial bearing.
+#[derive(Clone, Copy, Debug)]
+pub struct GreatCircle {
+    origin: [f64; 3],
+    heading: [f64; 3],
+    pole: [f64; 3],
+}
+
+impl GreatCircle {
+    /// `bearing` is in degrees clockwise from north.
+    pub fn new(lat: f64, lon: f64, bearing: f64) -> GreatCircle {
+        let (phi, lambda, theta) = (lat.to_radians(), lon.to_radians(), bearing.to_radians());
+
+        let origin = unit_vector(lat, lon);
+        let north = [-phi.sin() * lambda.cos(), -phi.sin() * lambda.sin(), phi.cos()];
+        let east = [-lambda.sin(), lambda.cos(), 0.0];
+        let heading = [
+            north[0] * theta.cos() + east[0] * theta.sin(),
+            north[1] * theta.cos() + east[1] * theta.sin(),
+            north[2] * theta.cos() + east[2] * theta.sin(),
+        ];
+        let pole = cross(origin, heading);
+
+        GreatCircle { origin, heading, pole }
+    }
+
+    /// Distance from a point to the great circle, in kilometres.
+    pub fn cross_track_km(&self, lat: f64, lon: f64) -> f64 {
+        dot(unit_vector(lat, lon), self.pole).clamp(-1.0, 1.0).asin().abs() * EARTH_RADIUS_KM
+    }
+
+    /// How far along the circle a point lies, as an angle in radians from the
+    /// origin in the direction of travel. The origin itself is at the very end
+    /// of the trip, `2π`, rather than the start.
+    pub fn along_track(&self, lat: f64, lon: f64) -> f64 {
+        let point = unit_vector(lat, lon);
+        let angle = dot(point, self.heading).atan2(dot(point, self.origin));
+
+        if angle <= 0.0 { angle + 2.0 * std::f64::consts::PI } else { angle }
+    }
+}
+
+/// The nearest of the eight principal compass points to a bearing.
+pub fn compass_point(bearing: f64) -> &'static str {
+    const POINTS: [&str; 8] = ["north", "northeast", "east", "southeast", "south", "southwest", "west", "northwest"];
+    let index = (bearing.rem_euclid(360.0) / 45.0).round() as usize % POINTS.len();
+
+    POINTS[index]
+}
+
+fn unit_vector(lat: f64, lon: f64) -> [f64; 3] {
+    let (phi, lambda) = (lat.to_radians(), lon.to_radians());
+    [phi.cos() * lambda.cos(), phi.cos() * lambda.sin(), phi.sin()]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn it_measures_great_circle_distance() {
+        // Pittsburgh to New York
+        assert_approx_eq!(haversine_km(40.4299986, -79.99998539, 40.74997906, -73.98001693), 509.5, 0.1);
+        assert_approx_eq!(haversine_km(10.0, 20.0, 10.0, 20.0), 0.0);
+        assert_approx_eq!(haversine_km(90.0, 0.0, -90.0, 0.0), MAX_DISTANCE_KM);
+    }
+
+    #[test]
+    fn it_follows_a_great_circle() {
+        let equator = GreatCircle::new(0.0, 0.0, 90.0);
+
+        assert_approx_eq!(equator.cross_track_km(0.0, 120.0), 0.0);
+        assert_approx_eq!(equator.cross_track_km(1.0, 50.0), KM_PER_DEGREE, 1e-6);
+        assert_approx_eq!(equator.along_track(0.0, 90.0), std::f64::consts::FRAC_PI_2);
+        assert_approx_eq!(equator.along_track(0.0, -90.0), 3.0 * std::f64::consts::FRAC_PI_2);
+        assert_approx_eq!(equator.along_track(0.0, 0.0), 2.0 * std::f64::consts::PI);
+
+        let meridian = GreatCircle::new(40.0, -80.0, 0.0);
+
+        assert_approx_eq!(meridian.cross_track_km(70.0, -80.0), 0.0);
+        assert_approx_eq!(meridian.cross_track_km(10.0, 100.0), 0.0);
+        assert!(meridian.along_track(70.0, -80.0) < meridian.along_track(10.0, 100.0));
+    }
+
+    #[test]
+    fn it_names_compass_points() {
+        assert_eq!(compass_point(0.0), "north");
+        assert_eq!(compass_point(45.0), "northeast");
+        assert_eq!(compass_point(135.0), "southeast");
+        assert_eq!(compass_point(350.0), "north");
+        assert_eq!(compass_point(-90.0), "west");
+    }
+}
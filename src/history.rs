@@ -0,0 +1,288 @@
+// A record of what the bot has posted, kept as one JSON object per line so
+// appending never rewrites the file, used to avoid posting a city again too
+// soon.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::{env, error, fmt, io};
+
+use super::{cli, City, CityId};
+
+/// One posted city.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub city: CityId,
+    /// Only to make the file readable; the id is what counts.
+    pub name: String,
+    pub posted_at: DateTime<Utc>,
+    /// The first status posted for the city, if it was posted at all.
+    pub status_id: Option<String>,
+}
+
+/// When a city may come round again.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Repeats {
+    /// Whenever it is picked.
+    #[default]
+    Allow,
+    /// Not within this long of the last time it was posted.
+    After(Duration),
+    /// Not until every other city has been posted since.
+    Tour,
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Io { path: String, error: io::Error },
+    Line { path: String, line: usize, error: serde_json::Error },
+    Setting(String),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::Io { path, error } =>
+                write!(f, "Failed to access history file {}: {}", path, error),
+            HistoryError::Line { path, line, error } =>
+                write!(f, "Invalid history in {} at line {}: {}", path, line, error),
+            HistoryError::Setting(message) =>
+                write!(f, "Invalid history setting: {}", message),
+        }
+    }
+}
+
+impl error::Error for HistoryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            HistoryError::Io { error, .. } => Some(error),
+            HistoryError::Line { error, .. } => Some(error),
+            HistoryError::Setting(_) => None,
+        }
+    }
+}
+
+/// Posted cities, oldest first, optionally backed by a file.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+impl History {
+    /// A history kept only in memory.
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Reads the history file at `path`; a file that does not exist yet is an
+    /// empty history, created on the first `record`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<History, HistoryError> {
+        let path = path.as_ref();
+        let source = path.display().to_string();
+        let mut entries = Vec::new();
+
+        match File::open(path) {
+            Ok(file) => {
+                for (index, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line.map_err(|error| HistoryError::Io { path: source.clone(), error })?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let entry = serde_json::from_str(&line)
+                        .map_err(|error| HistoryError::Line { path: source.clone(), line: index + 1, error })?;
+                    entries.push(entry);
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(HistoryError::Io { path: source, error }),
+        }
+
+        Ok(History { path: Some(path.to_path_buf()), entries })
+    }
+
+    /// A copy kept only in memory, for trying out what would be posted
+    /// without touching the file.
+    pub fn snapshot(&self) -> History {
+        History { path: None, entries: self.entries.clone() }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Adds an entry, appending it to the file if there is one.
+    pub fn record(&mut self, entry: Entry) -> Result<(), HistoryError> {
+        if let Some(path) = &self.path {
+            let source = path.display().to_string();
+            let mut line = serde_json::to_string(&entry).map_err(|error| HistoryError::Line {
+                path: source.clone(),
+                line: self.entries.len() + 1,
+                error,
+            })?;
+            line.push('\n');
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+                .map_err(|error| HistoryError::Io { path: source, error })?;
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// The cities of `candidates` that `repeats` rules out at `now`.
+    pub fn to_avoid(&self, candidates: &[&City], repeats: Repeats, now: DateTime<Utc>) -> HashSet<CityId> {
+        match repeats {
+            Repeats::Allow => HashSet::new(),
+            Repeats::After(window) => self.entries
+                .iter()
+                .filter(|entry| entry.posted_at > now - window)
+                .map(|entry| entry.city)
+                .collect(),
+            Repeats::Tour => {
+                let all: HashSet<CityId> = candidates.iter().map(|city| city.id()).collect();
+                // Replay the history, starting a new tour whenever every
+                // candidate has been visited.
+                let mut visited = HashSet::new();
+                for entry in &self.entries {
+                    if all.contains(&entry.city) {
+                        visited.insert(entry.city);
+                    }
+                    if visited.len() == all.len() {
+                        visited.clear();
+                    }
+                }
+                visited
+            }
+        }
+    }
+}
+
+/// The history file from `--history <path>` or `TOOT_HISTORY`, if any.
+pub fn history_path<I: IntoIterator<Item = String>>(args: I) -> Option<PathBuf> {
+    cli::setting(args, "--history", "TOOT_HISTORY").map(PathBuf::from)
+}
+
+/// Reads `--tour` or `TOOT_TOUR`, then `--repeat-after <days>` or
+/// `TOOT_REPEAT_AFTER_DAYS`.
+pub fn repeats_arg<I: IntoIterator<Item = String>>(args: I) -> Result<Repeats, HistoryError> {
+    let args: Vec<String> = args.into_iter().collect();
+
+    if cli::has_flag(args.clone(), "--tour") || env::var("TOOT_TOUR").is_ok_and(|tour| tour == "1" || tour == "true") {
+        return Ok(Repeats::Tour);
+    }
+    match cli::setting(args, "--repeat-after", "TOOT_REPEAT_AFTER_DAYS") {
+        Some(days) => match days.parse::<u32>() {
+            Ok(days) => Ok(Repeats::After(Duration::days(i64::from(days)))),
+            Err(_) => Err(HistoryError::Setting(format!("`{}` is not a number of days", days))),
+        },
+        None => Ok(Repeats::Allow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args;
+    use chrono::TimeZone;
+
+    fn city(name: &str) -> City {
+        City::new(name, 0.0, 0.0, 0.0, "Testland", "").unwrap()
+    }
+
+    fn entry(city: &City, day: u32) -> Entry {
+        Entry {
+            city: city.id(),
+            name: city.name().to_string(),
+            posted_at: Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap(),
+            status_id: Some(format!("{}", day)),
+        }
+    }
+
+    #[test]
+    fn it_keeps_the_history_in_a_file() {
+        let path = env::temp_dir().join(format!("toot-history-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pittsburgh = city("Pittsburgh");
+
+        let mut history = History::open(&path).unwrap();
+        assert!(history.entries().is_empty());
+        history.record(entry(&pittsburgh, 1)).unwrap();
+        history.record(entry(&pittsburgh, 2)).unwrap();
+
+        let reopened = History::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.entries(), history.entries());
+        assert_eq!(reopened.entries()[1].status_id.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn it_leaves_the_file_alone_when_recording_in_a_snapshot() {
+        let path = env::temp_dir().join(format!("toot-history-snapshot-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pittsburgh = city("Pittsburgh");
+        let mut history = History::open(&path).unwrap();
+        history.record(entry(&pittsburgh, 1)).unwrap();
+
+        let mut snapshot = history.snapshot();
+        snapshot.record(entry(&pittsburgh, 2)).unwrap();
+
+        let reopened = History::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot.entries().len(), 2);
+        assert_eq!(reopened.entries(), history.entries());
+    }
+
+    #[test]
+    fn it_names_the_line_of_a_bad_entry() {
+        let path = env::temp_dir().join(format!("toot-history-bad-{}.jsonl", std::process::id()));
+        std::fs::write(&path, "\nnot json\n").unwrap();
+
+        let error = History::open(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(error, HistoryError::Line { line: 2, .. }));
+    }
+
+    #[test]
+    fn it_avoids_cities_posted_within_the_window() {
+        let (a, b) = (city("A"), city("B"));
+        let mut history = History::new();
+        history.record(entry(&a, 1)).unwrap();
+        history.record(entry(&b, 5)).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 3, 7, 12, 0, 0).unwrap();
+
+        assert_eq!(history.to_avoid(&[&a, &b], Repeats::After(Duration::days(3)), now), HashSet::from([b.id()]));
+        assert!(history.to_avoid(&[&a, &b], Repeats::Allow, now).is_empty());
+    }
+
+    #[test]
+    fn it_tours_every_city_before_repeating() {
+        let (a, b, c) = (city("A"), city("B"), city("C"));
+        let mut history = History::new();
+        let now = Utc.with_ymd_and_hms(2024, 3, 7, 12, 0, 0).unwrap();
+
+        history.record(entry(&a, 1)).unwrap();
+        history.record(entry(&b, 2)).unwrap();
+        assert_eq!(history.to_avoid(&[&a, &b, &c], Repeats::Tour, now), HashSet::from([a.id(), b.id()]));
+
+        // Once C is posted the tour is complete and the next one starts afresh.
+        history.record(entry(&c, 3)).unwrap();
+        assert!(history.to_avoid(&[&a, &b, &c], Repeats::Tour, now).is_empty());
+        history.record(entry(&b, 4)).unwrap();
+        assert_eq!(history.to_avoid(&[&a, &b, &c], Repeats::Tour, now), HashSet::from([b.id()]));
+    }
+
+    #[test]
+    fn it_reads_the_repeat_settings() {
+        assert_eq!(repeats_arg(args(&["toot", "--repeat-after", "7"])).unwrap(), Repeats::After(Duration::days(7)));
+        assert_eq!(repeats_arg(args(&["toot", "--tour", "--repeat-after", "7"])).unwrap(), Repeats::Tour);
+        assert!(repeats_arg(args(&["toot", "--repeat-after", "soon"])).is_err());
+        assert_eq!(history_path(args(&["toot", "--history", "/var/lib/toot/history.jsonl"])), Some(PathBuf::from("/var/lib/toot/history.jsonl")));
+    }
+}
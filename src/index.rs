@@ -0,0 +1,60 @@
+// Sorted coordinate indexes so band queries are range lookups instead of full
+// scans over every city.
+
+use super::City;
+
+/// Positions into a city list, ordered by one coordinate.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SortedIndex {
+    order: Vec<usize>,
+    keys: Vec<f64>,
+}
+
+impl SortedIndex {
+    pub(crate) fn new(cities: &[City], key: fn(&City) -> f64) -> SortedIndex {
+        let mut order: Vec<usize> = (0..cities.len()).collect();
+        order.sort_by(|&a, &b| key(&cities[a]).total_cmp(&key(&cities[b])));
+        let keys = order.iter().map(|&i| key(&cities[i])).collect();
+
+        SortedIndex { order, keys }
+    }
+
+    /// Positions of the cities whose key lies strictly between `low` and `high`.
+    pub(crate) fn between(&self, low: f64, high: f64) -> &[usize] {
+        let start = self.keys.partition_point(|&k| k <= low);
+        let end = self.keys.partition_point(|&k| k < high);
+
+        &self.order[start..end.max(start)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn city(latitude: f64) -> City {
+        City {
+            name: String::new(),
+            city_ascii: String::new(),
+            latitude,
+            longitude: 0.0,
+            population: 0.0,
+            country: String::new(),
+            iso2: String::new(),
+            iso3: String::new(),
+            province: String::new(),
+        }
+    }
+
+    #[test]
+    fn it_finds_positions_strictly_inside_the_band() {
+        let cities = vec![city(3.0), city(1.0), city(2.0), city(1.5), city(0.0)];
+        let index = SortedIndex::new(&cities, |c| c.latitude);
+
+        let mut found = index.between(1.0, 3.0).to_vec();
+        found.sort();
+
+        assert_eq!(found, vec![2, 3]);
+        assert!(index.between(3.0, 1.0).is_empty());
+    }
+}
@@ -1,23 +1,268 @@
+use chrono::{DateTime, Utc};
 use itertools::{Itertools, Either};
-use rand::prelude::IndexedRandom;
+use rand::Rng;
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use std::fmt;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::{error, fmt};
+use std::io::Read;
+use std::path::Path;
 use std::convert::From;
-
-mod data_loader;
-
-#[derive(Deserialize, PartialEq, Clone, Debug)]
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+pub mod cli;
+pub mod compose;
+pub mod continent;
+pub mod data_loader;
+pub mod geo;
+pub mod history;
+mod index;
+pub mod locale;
+pub mod report;
+pub mod schedule;
+pub mod search;
+pub mod seed;
+pub mod selection;
+pub mod template;
+#[cfg(feature = "embedded-data")]
+mod embedded_data;
+
+use compose::ComposeError;
+use data_loader::LoadError;
+use history::{History, Repeats};
+use index::SortedIndex;
+use locale::Locale;
+use report::{Antipode, Coordinates, Location, Place};
+use search::{CityMatch, Query};
+use selection::Selection;
+use template::{Context, Template, Templates, Value};
+
+/// A named place with its position, population and country.
+///
+/// Build one with `City::new`, which checks the coordinates and population.
+/// Two cities are equal, and hash the same, when they have the same `id`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct City {
     name: String,
+    /// The name spelled without diacritics, as given in the dataset.
+    city_ascii: String,
     latitude: f64,
     longitude: f64,
     population: f64,
     country: String,
+    /// ISO 3166-1 alpha-2 country code, such as `US`; empty for the poles.
+    iso2: String,
+    /// ISO 3166-1 alpha-3 country code, such as `USA`.
+    iso3: String,
     province: String,
 }
 
+/// Identifies a city by its name, country, province and coordinates, so the
+/// same row gets the same id in every run and every build.
+///
+/// It is written out, in JSON as anywhere else, as the 16 hex digits shown by
+/// `Display`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CityId(u64);
+
+impl fmt::Display for CityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl Serialize for CityId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CityId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<CityId, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        u64::from_str_radix(&id, 16)
+            .map(CityId)
+            .map_err(|_| serde::de::Error::custom(format!("`{}` is not a city id", id)))
+    }
+}
+
+/// Why `City::new` rejected a city.
+#[derive(Debug, PartialEq)]
+pub enum CityError {
+    Latitude(f64),
+    Longitude(f64),
+    Population(f64),
+}
+
+impl fmt::Display for CityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CityError::Latitude(latitude) =>
+                write!(f, "latitude {} is outside [-90, 90]", latitude),
+            CityError::Longitude(longitude) =>
+                write!(f, "longitude {} is outside [-180, 180]", longitude),
+            CityError::Population(population) =>
+                write!(f, "population {} is negative", population),
+        }
+    }
+}
+
+impl error::Error for CityError {}
+
+impl City {
+    /// A city with no ASCII spelling or country codes of its own: the ASCII
+    /// name is the name without diacritics, and the codes are empty until set
+    /// with `with_iso_codes`.
+    pub fn new(
+        name: impl Into<String>,
+        latitude: f64,
+        longitude: f64,
+        population: f64,
+        country: impl Into<String>,
+        province: impl Into<String>,
+    ) -> Result<City, CityError> {
+        if !(-90.0..=90.0).contains(&latitude) {
+            return Err(CityError::Latitude(latitude));
+        }
+        if !(-180.0..=180.0).contains(&longitude) {
+            return Err(CityError::Longitude(longitude));
+        }
+        if population.is_nan() || population < 0.0 {
+            return Err(CityError::Population(population));
+        }
+
+        let name = name.into();
+        Ok(City {
+            city_ascii: remove_diacritics(&name),
+            name,
+            latitude,
+            longitude,
+            population,
+            country: country.into(),
+            iso2: String::new(),
+            iso3: String::new(),
+            province: province.into(),
+        })
+    }
+
+    /// Replaces the ASCII spelling worked out by `new`.
+    pub fn with_city_ascii(self, city_ascii: impl Into<String>) -> City {
+        City { city_ascii: city_ascii.into(), ..self }
+    }
+
+    /// Sets the ISO 3166-1 alpha-2 and alpha-3 codes of the city's country.
+    pub fn with_iso_codes(self, iso2: impl Into<String>, iso3: impl Into<String>) -> City {
+        City { iso2: iso2.into(), iso3: iso3.into(), ..self }
+    }
+
+    /// Hashes the name, country, province and the coordinates rounded to a
+    /// millionth of a degree with 64-bit FNV-1a, which unlike the standard
+    /// library's hasher is fixed across Rust releases.
+    pub fn id(&self) -> CityId {
+        let mut hash: u64 = FNV_OFFSET_BASIS;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        for text in [&self.name, &self.country, &self.province] {
+            write(text.as_bytes());
+            write(&[0xff]);
+        }
+        for coordinate in [self.latitude, self.longitude] {
+            write(&((coordinate / ID_COORDINATE_STEP).round() as i64).to_le_bytes());
+        }
+        CityId(hash)
+    }
+
+    /// The name as the dataset spells it, diacritics and all.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name without diacritics, such as "Darien" for "Darién".
+    pub fn city_ascii(&self) -> &str {
+        &self.city_ascii
+    }
+
+    /// Degrees north of the equator; negative in the south.
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    /// Degrees east of Greenwich; negative in the west.
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    /// The number of inhabitants; the dataset gives some as fractions.
+    pub fn population(&self) -> f64 {
+        self.population
+    }
+
+    /// The country's name in English, such as "United States of America".
+    pub fn country(&self) -> &str {
+        &self.country
+    }
+
+    /// The state, province or region; empty where the dataset has none.
+    pub fn province(&self) -> &str {
+        &self.province
+    }
+
+    /// The ISO 3166-1 alpha-2 country code as the dataset gives it, such as
+    /// `US`; see `continent::iso2_of` for the ones it gets wrong.
+    pub fn iso2(&self) -> &str {
+        &self.iso2
+    }
+
+    /// The ISO 3166-1 alpha-3 country code, such as `USA`.
+    pub fn iso3(&self) -> &str {
+        &self.iso3
+    }
+
+    /// The flag emoji of the city's country, made from the regional indicator
+    /// symbols for its `iso2` code as corrected by `continent::iso2_of`, or an
+    /// empty string without a valid code.
+    pub fn flag(&self) -> String {
+        let iso2 = match continent::iso2_of(&self.iso2, &self.iso3) {
+            Some(iso2) if iso2.len() == 2 && iso2.chars().all(|c| c.is_ascii_alphabetic()) => iso2,
+            _ => return String::new(),
+        };
+        iso2
+            .to_ascii_uppercase()
+            .chars()
+            .filter_map(|c| char::from_u32(REGIONAL_INDICATOR_A + (c as u32 - 'A' as u32)))
+            .collect()
+    }
+}
+
+const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// Ids round coordinates to this many degrees, about 10 cm.
+const ID_COORDINATE_STEP: f64 = 1e-6;
+
+impl PartialEq for City {
+    fn eq(&self, other: &City) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for City {}
+
+impl Hash for City {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
 impl fmt::Display for City {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.name)
@@ -25,70 +270,686 @@ impl fmt::Display for City {
 }
 
 lazy_static! {
-    static ref DATA: Vec<City> = data_loader::load_data().unwrap();
-
     static ref NORTH_POLE: City = City {
         name: String::from("North Pole"),
+        city_ascii: String::from("North Pole"),
         latitude: 90.0,
         longitude: 0.0,
         population: 0.0,
         country: String::from("North Pole"),
+        iso2: String::new(),
+        iso3: String::new(),
         province: String::from("North Pole"),
     };
 
     static ref SOUTH_POLE: City = City {
         name: String::from("South Pole"),
+        city_ascii: String::from("South Pole"),
         latitude: -90.0,
         longitude: 0.0,
         population: 0.0,
         country: String::from("Antarctica"),
+        iso2: String::new(),
+        iso3: String::new(),
         province: String::from("South Pole"),
     };
 }
 
-const LATITUDE_TOLERANCE: f64 = 0.5;
+/// Default half-width of the strip either side of a parallel, meridian or
+/// great-circle route: half a degree of latitude, about 55.6 km.
+pub const DEFAULT_CORRIDOR_KM: f64 = 0.5 * geo::KM_PER_DEGREE;
+
+const NEAREST_START_RADIUS_KM: f64 = 50.0;
+
+/// How the cities along a route are chosen.
+#[derive(Clone, Debug)]
+pub struct RouteOptions {
+    /// How many cities to name, counting the origin when it is included but
+    /// not the poles.
+    pub cities: usize,
+    /// Mark the North and South Poles on routes along a meridian.
+    pub include_poles: bool,
+    /// End the route with the city it starts from. The origin is then left out
+    /// of the ranking so it is never named twice.
+    pub include_origin: bool,
+    /// Leave out cities with fewer people than this.
+    pub min_population: f64,
+    /// Half-width in kilometres of the strip searched either side of the route.
+    pub corridor_km: f64,
+}
 
-const NUM_CITIES: usize = 10;
-// The cities with the highest population may or may not include the current city;
-// I want to be sure I'm always including the current city exactly once and last.
-// So unconditionally remove the last sorted east/north and add on the current city
-// instead.
-const NUM_CITIES_LATITUDE: usize = 9;
-// North Pole and South Pole are always included in the longitude text, but they
-// aren't particularly interesting; I want to return 10 cities plus them.
-const NUM_CITIES_LONGITUDE: usize = 11;
+impl Default for RouteOptions {
+    fn default() -> RouteOptions {
+        RouteOptions {
+            cities: 10,
+            include_poles: true,
+            include_origin: true,
+            min_population: 0.0,
+            corridor_km: DEFAULT_CORRIDOR_KM,
+        }
+    }
+}
 
-pub fn random_location() -> String {
-    let mut rng = rand::rng();
-    let city = DATA.choose(&mut rng).unwrap();
+/// How wordy the generated sentences are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Phrasing {
+    #[default]
+    Full,
+    Short,
+}
 
-    location_text(city)
+/// How place names are spelled in a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameStyle {
+    /// As written locally, such as "Darién".
+    #[default]
+    Native,
+    /// Without diacritics, such as "Darien", for clients that mangle them.
+    Ascii,
 }
 
-pub fn location_text(city: &City) -> String {
-    format!("You are now in {}, {}, {}
-{} {}
-{}
-{}",
-        city,
-        city.province,
-        city.country,
-        latitude_in_degrees(city.latitude),
-        longitude_in_degrees(city.longitude),
-        latitude_text(&city),
-        longitude_text(&city),
-    )
+/// Optional extras for `Gazetteer::location_text_with`.
+#[derive(Clone, Debug, Default)]
+pub struct TextOptions {
+    /// Add a line naming the city nearest the antipode.
+    pub antipode: bool,
+    /// How the latitude and longitude routes are built.
+    pub route: RouteOptions,
+    pub phrasing: Phrasing,
+    /// The language the text is written in, with its sentences and number
+    /// formats.
+    pub locale: Locale,
+    /// Follow each city's name with the flag of its country.
+    pub flags: bool,
+    pub names: NameStyle,
+    /// Hashtags added at the end of the text, such as `Geography` or `{city}`;
+    /// see `template::hashtags`.
+    pub hashtags: Vec<Template>,
 }
 
-fn same_latitude(lat: f64) -> Vec<City> {
-    DATA
-        .iter()
-        .cloned()
-        .filter(|city| {
-            city.latitude < lat + LATITUDE_TOLERANCE &&
-            lat - LATITUDE_TOLERANCE < city.latitude
+impl TextOptions {
+    fn templates(&self) -> &Templates {
+        match self.phrasing {
+            Phrasing::Full => &self.locale.phrasebook.full,
+            Phrasing::Short => &self.locale.phrasebook.short,
+        }
+    }
+}
+
+/// An owned collection of cities that all location queries run against.
+///
+/// A `Gazetteer` can be built from any list of cities, so several datasets can
+/// live side by side and tests can use small fixtures instead of the full CSV.
+/// Latitude and longitude indexes are built once here, so band queries only
+/// look at the cities inside the band.
+#[derive(Clone, Debug, Default)]
+pub struct Gazetteer {
+    cities: Vec<City>,
+    by_id: HashMap<CityId, usize>,
+    by_latitude: SortedIndex,
+    by_longitude: SortedIndex,
+    /// Cities so near a pole that a strip of any useful width spans most of
+    /// the globe in degrees; they are checked on every longitude query.
+    polar: Vec<usize>,
+    /// Furthest any other city is from the equator, which sizes the index
+    /// lookup for longitude queries.
+    max_abs_latitude: f64,
+}
+
+/// Beyond this latitude a city is checked on every longitude query instead of
+/// widening the index lookup for everyone.
+const POLAR_LATITUDE: f64 = 80.0;
+
+impl Gazetteer {
+    pub fn new(cities: Vec<City>) -> Gazetteer {
+        let by_latitude = SortedIndex::new(&cities, |city| city.latitude);
+        let by_longitude = SortedIndex::new(&cities, |city| city.longitude);
+        let (polar, others): (Vec<usize>, Vec<usize>) =
+            (0..cities.len()).partition(|&i| cities[i].latitude.abs() > POLAR_LATITUDE);
+        let max_abs_latitude = others.iter().map(|&i| cities[i].latitude.abs()).fold(0.0, f64::max);
+        let mut by_id = HashMap::with_capacity(cities.len());
+        for (i, city) in cities.iter().enumerate() {
+            by_id.entry(city.id()).or_insert(i);
+        }
+
+        Gazetteer { cities, by_id, by_latitude, by_longitude, polar, max_abs_latitude }
+    }
+
+    /// Loads the world cities dataset from `TOOT_DATA_PATH`, or the default
+    /// dataset when it is not set.
+    pub fn load() -> Result<Gazetteer, LoadError> {
+        data_loader::load_data().map(Gazetteer::new)
+    }
+
+    /// Loads from `explicit` if given, otherwise behaves like `load`.
+    pub fn load_configured(explicit: Option<&Path>) -> Result<Gazetteer, LoadError> {
+        data_loader::load_configured(explicit).map(Gazetteer::new)
+    }
+
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Gazetteer, LoadError> {
+        data_loader::load_from_path(path).map(Gazetteer::new)
+    }
+
+    pub fn load_from_reader<R: Read>(reader: R) -> Result<Gazetteer, LoadError> {
+        data_loader::load_from_reader(reader).map(Gazetteer::new)
+    }
+
+    pub fn cities(&self) -> &[City] {
+        &self.cities
+    }
+
+    /// The city with the given id; the first one if several share it.
+    pub fn city(&self, id: CityId) -> Option<&City> {
+        self.by_id.get(&id).map(|&i| &self.cities[i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.cities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cities.is_empty()
+    }
+
+    /// Cities called `name`, matching either the native or the ASCII spelling
+    /// and ignoring case, in dataset order.
+    pub fn cities_named(&self, name: &str) -> Vec<&City> {
+        let name = name.trim().to_lowercase();
+        self.cities
+            .iter()
+            .filter(|city| city.name.to_lowercase() == name || city.city_ascii.to_lowercase() == name)
+            .collect()
+    }
+
+    /// Cities whose name is close to `query`, best match first.
+    ///
+    /// Case, diacritics and punctuation are ignored, either spelling of a name
+    /// matches, and a typo or two is forgiven in longer names. Anything after a
+    /// comma must match the province or country, by name or ISO code, so
+    /// "Springfield, Illinois" picks one of several Springfields. Equally close
+    /// matches are ranked by population.
+    pub fn find_city(&self, query: &str) -> Vec<CityMatch<'_>> {
+        let query = Query::parse(query);
+        if query.name.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<_> = self.cities
+            .iter()
+            .filter_map(|city| query.distance(city).map(|distance| CityMatch { city, distance }))
+            .collect();
+        matches.sort_by(|a, b| a.distance.cmp(&b.distance).then(b.city.population.total_cmp(&a.city.population)));
+        matches
+    }
+
+    /// Picks a city uniformly at random, or `None` if the gazetteer is empty.
+    pub fn random_city(&self) -> Option<&City> {
+        self.random_city_with_rng(&mut rand::rng())
+    }
+
+    /// Picks a city uniformly with the given generator, so a seeded generator
+    /// picks the same city every time. This is the default `Selection`, so the
+    /// binaries pick the same city for the same seed.
+    pub fn random_city_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&City> {
+        self.select_city(&Selection::default(), rng)
+    }
+
+    /// Picks a city the way `selection` says, or `None` if no city qualifies.
+    pub fn select_city<R: Rng + ?Sized>(&self, selection: &Selection, rng: &mut R) -> Option<&City> {
+        selection.pick(self.cities.iter().collect(), rng)
+    }
+
+    /// Picks a city the way `selection` says, skipping those `repeats` rules
+    /// out given `history`. If that leaves nothing, a repeat is better than
+    /// no post at all.
+    pub fn select_fresh_city<R: Rng + ?Sized>(
+        &self,
+        selection: &Selection,
+        history: &History,
+        repeats: Repeats,
+        now: DateTime<Utc>,
+        rng: &mut R,
+    ) -> Option<&City> {
+        let eligible: Vec<&City> = self.cities.iter().filter(|city| city.population >= selection.min_population).collect();
+        let avoid = history.to_avoid(&eligible, repeats, now);
+        let fresh = eligible.iter().filter(|city| !avoid.contains(&city.id())).copied().collect();
+        selection.pick(fresh, rng).or_else(|| selection.pick(eligible, rng))
+    }
+
+    /// Picks the same city for the same seed and dataset; see `seed::rng`.
+    pub fn seeded_city(&self, seed: u64) -> Option<&City> {
+        self.random_city_with_rng(&mut seed::rng(Some(seed)))
+    }
+
+    pub fn random_location(&self) -> Option<String> {
+        self.random_location_with(&TextOptions::default())
+    }
+
+    pub fn random_location_with(&self, options: &TextOptions) -> Option<String> {
+        self.random_location_with_rng(options, &mut rand::rng())
+    }
+
+    pub fn random_location_with_rng<R: Rng + ?Sized>(&self, options: &TextOptions, rng: &mut R) -> Option<String> {
+        self.random_city_with_rng(rng).map(|city| self.location_text_with(city, options))
+    }
+
+    pub fn location_text(&self, city: &City) -> String {
+        self.location_text_with(city, &TextOptions::default())
+    }
+
+    pub fn location_text_with(&self, city: &City, options: &TextOptions) -> String {
+        self.location_parts(city, options).join("\n")
+    }
+
+    /// The pieces of a location text, each of which can stand on its own in a
+    /// thread: where you are, the latitude route, the longitude route and, if
+    /// asked for, the antipode.
+    pub fn location_parts(&self, city: &City, options: &TextOptions) -> Vec<String> {
+        let templates = options.templates();
+        let mut context = city_context(city, options);
+        context.insert("route_east", route_value(&self.latitude_cities(city, &options.route), options));
+        context.insert("route_north", route_value(&self.longitude_cities(city, &options.route), options));
+
+        let mut parts = vec![
+            templates.header.render(&context),
+            templates.latitude.render(&context),
+            templates.longitude.render(&context),
+        ];
+
+        if options.antipode && let Some(antipode) = self.antipode_context(city, options) {
+            context.extend(antipode);
+            parts.push(templates.antipode.render(&context));
+        }
+
+        let hashtags = hashtags(&options.hashtags, &context);
+        if !hashtags.is_empty() && let Some(last) = parts.last_mut() {
+            last.push('\n');
+            last.push_str(&hashtags);
+        }
+
+        parts
+    }
+
+    /// The same facts as `location_parts`, as data instead of sentences.
+    pub fn location(&self, city: &City, options: &TextOptions) -> Location {
+        let places = |cities: Vec<&City>| cities.into_iter().map(Place::from).collect();
+        let antipode = if options.antipode {
+            self.antipode_cities(city.latitude, city.longitude, 1).into_iter().next().map(|(near, distance)| {
+                let (latitude, longitude) = antipode(city.latitude, city.longitude);
+                Antipode { latitude, longitude, nearest: Place::from(near), distance_km: distance }
+            })
+        } else {
+            None
+        };
+
+        Location {
+            origin: Place::from(city),
+            coordinates: Coordinates {
+                latitude: city.latitude,
+                longitude: city.longitude,
+                lat_dms: options.locale.latitude_dms(city.latitude),
+                lon_dms: options.locale.longitude_dms(city.longitude),
+            },
+            latitude_route: places(self.latitude_cities(city, &options.route)),
+            longitude_route: places(self.longitude_cities(city, &options.route)),
+            antipode,
+        }
+    }
+
+    /// Describes an arbitrary point by the nearest city in the gazetteer, or
+    /// `None` if the gazetteer is empty.
+    ///
+    /// The text is always in English: locales and templates only reach the
+    /// texts about a city, such as `location_text_with`.
+    pub fn location_text_for_point(&self, lat: f64, lon: f64) -> Option<String> {
+        let (city, distance) = self.nearest(lat, lon, 1).into_iter().next()?;
+
+        Some(format!("You are near {}, {}, {} ({:.0} km away)
+{} {}",
+            city,
+            city.province,
+            city.country,
+            distance,
+            latitude_in_degrees(lat),
+            longitude_in_degrees(lon),
+        ))
+    }
+
+    /// The `k` cities closest to the point on the opposite side of the Earth,
+    /// with their distances from that point, nearest first.
+    pub fn antipode_cities(&self, lat: f64, lon: f64, k: usize) -> Vec<(&City, f64)> {
+        let (antipode_lat, antipode_lon) = antipode(lat, lon);
+        self.nearest(antipode_lat, antipode_lon, k)
+    }
+
+    /// The sentence about the antipode of `city` in the locale and phrasing
+    /// of `options`, or `None` if the gazetteer is empty.
+    pub fn antipode_text(&self, city: &City, options: &TextOptions) -> Option<String> {
+        let mut context = city_context(city, options);
+        context.extend(self.antipode_context(city, options)?);
+
+        Some(options.templates().antipode.render(&context))
+    }
+
+    fn antipode_context(&self, city: &City, options: &TextOptions) -> Option<Context> {
+        let (near, distance) = self.antipode_cities(city.latitude, city.longitude, 1).into_iter().next()?;
+
+        Some(Context::from([
+            ("antipode_city", Value::Text(display_name(near, options))),
+            ("antipode_province", Value::Text(spelled(&near.province, options))),
+            ("antipode_country", Value::Text(spelled(&near.country, options))),
+            ("antipode_km", Value::Text(options.locale.format_number(distance, 0))),
+        ]))
+    }
+
+    /// Builds the location text for `city` so it fits in `budget` characters
+    /// as Mastodon counts them. The routes lose their least populous cities
+    /// first, then the wording gets shorter; names are never cut.
+    pub fn compose_location(&self, city: &City, options: &TextOptions, budget: usize) -> Result<String, ComposeError> {
+        let mut shortest = usize::MAX;
+        let mut phrasings = vec![options.phrasing];
+        if options.phrasing != Phrasing::Short {
+            phrasings.push(Phrasing::Short);
+        }
+
+        for phrasing in phrasings {
+            for cities in (1..=options.route.cities.max(1)).rev() {
+                let attempt = TextOptions {
+                    phrasing,
+                    route: RouteOptions { cities, ..options.route.clone() },
+                    ..options.clone()
+                };
+                let text = self.location_text_with(city, &attempt);
+                let length = compose::status_length(&text);
+                if length <= budget {
+                    return Ok(text);
+                }
+                shortest = shortest.min(length);
+            }
+        }
+
+        Err(ComposeError { budget, shortest })
+    }
+
+    /// Like `compose_location`, but rather than shortening the wording, splits
+    /// the text into statuses to post as a thread: the header first and each
+    /// route as a reply. Everything stays in one status when it fits.
+    pub fn compose_thread(&self, city: &City, options: &TextOptions, budget: usize) -> Result<Vec<String>, ComposeError> {
+        let mut shortest = usize::MAX;
+
+        for cities in (1..=options.route.cities.max(1)).rev() {
+            let attempt = TextOptions {
+                route: RouteOptions { cities, ..options.route.clone() },
+                ..options.clone()
+            };
+            let parts = self.location_parts(city, &attempt);
+            let whole = parts.join("\n");
+            if compose::status_length(&whole) <= budget {
+                return Ok(vec![whole]);
+            }
+            let longest = parts.iter().map(|part| compose::status_length(part)).max().unwrap_or(0);
+            if longest <= budget {
+                return Ok(parts);
+            }
+            shortest = shortest.min(longest);
+        }
+
+        Err(ComposeError { budget, shortest })
+    }
+
+    /// The `k` cities closest to a point with their great-circle distances in
+    /// kilometres, nearest first.
+    pub fn nearest(&self, lat: f64, lon: f64, k: usize) -> Vec<(&City, f64)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        // Widen the search until it holds k cities; anything outside the
+        // radius is further away than everything inside it.
+        let mut radius = NEAREST_START_RADIUS_KM;
+        loop {
+            let mut found = self.within_radius(lat, lon, radius);
+            if found.len() >= k || radius >= geo::MAX_DISTANCE_KM {
+                found.truncate(k);
+                return found;
+            }
+            radius *= 2.0;
+        }
+    }
+
+    /// Every city within `km` kilometres of a point with its great-circle
+    /// distance, nearest first.
+    pub fn within_radius(&self, lat: f64, lon: f64, km: f64) -> Vec<(&City, f64)> {
+        // A city within `km` is never more than `km` of latitude away, so only
+        // that band needs a distance check. The margin keeps cities exactly on
+        // the edge of the band.
+        let band = km / geo::KM_PER_DEGREE + 1e-9;
+
+        let mut found: Vec<_> = self.by_latitude
+            .between(lat - band, lat + band)
+            .iter()
+            .map(|&i| {
+                let city = &self.cities[i];
+                (city, geo::haversine_km(lat, lon, city.latitude, city.longitude))
+            })
+            .filter(|&(_, distance)| distance <= km)
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found
+    }
+
+    /// Cities within `corridor_km` north or south of the parallel at `lat`.
+    pub fn same_latitude(&self, lat: f64, corridor_km: f64) -> Vec<&City> {
+        let half_width = corridor_km / geo::KM_PER_DEGREE;
+        let positions = self.by_latitude.between(lat - half_width, lat + half_width);
+
+        self.in_dataset_order(positions.to_vec())
+    }
+
+    /// Cities within `corridor_km` east or west of the meridian at `long`, or
+    /// of its continuation on the other side of the globe.
+    ///
+    /// A degree of longitude shrinks towards the poles, so the width in degrees
+    /// is worked out from each city's own latitude to keep the strip the same
+    /// width on the ground.
+    pub fn same_longitude(&self, long: f64, corridor_km: f64) -> Vec<&City> {
+        let opposite_long = opposite_longitude(long);
+
+        let mut positions = self.meridian_strip(long, corridor_km);
+        positions.extend(self.meridian_strip(opposite_long, corridor_km));
+
+        self.in_dataset_order(positions)
+    }
+
+    fn meridian_strip(&self, long: f64, corridor_km: f64) -> Vec<usize> {
+        self.meridian_candidates(long, corridor_km)
+            .into_iter()
+            .filter(|&i| {
+                let city = &self.cities[i];
+                longitude_difference(city.longitude, long) < degrees_of_longitude(corridor_km, city.latitude)
+            })
+            .collect()
+    }
+
+    // The cities that might lie in the strip: the polar ones, plus the index
+    // band as wide in degrees as the non-polar city furthest from the equator
+    // needs. Only a corridor too wide for the index falls back to every city.
+    fn meridian_candidates(&self, long: f64, corridor_km: f64) -> Vec<usize> {
+        let widest = degrees_of_longitude(corridor_km, self.max_abs_latitude);
+        if widest >= 180.0 {
+            return (0..self.cities.len()).collect();
+        }
+
+        let (low, high) = (long - widest, long + widest);
+        let mut candidates = self.by_longitude.between(low, high).to_vec();
+        if low < -180.0 {
+            candidates.extend_from_slice(self.by_longitude.between(low + 360.0, f64::INFINITY));
+        }
+        if high > 180.0 {
+            candidates.extend_from_slice(self.by_longitude.between(f64::NEG_INFINITY, high - 360.0));
+        }
+        candidates.extend_from_slice(&self.polar);
+        candidates
+    }
+
+    // Index lookups come back ordered by coordinate; put them back in dataset
+    // order so ties in population rank the same way a plain scan would.
+    fn in_dataset_order(&self, mut positions: Vec<usize>) -> Vec<&City> {
+        positions.sort_unstable();
+        positions.dedup();
+        positions.into_iter().map(|i| &self.cities[i]).collect()
+    }
+
+    /// The most populous cities near the great circle leaving `origin` on
+    /// `bearing`, in the order you fly over them.
+    pub fn route_cities<'a>(&'a self, origin: &'a City, bearing: f64, options: &RouteOptions) -> Vec<&'a City> {
+        let route = geo::GreatCircle::new(origin.latitude, origin.longitude, bearing);
+
+        let candidates = self.cities
+            .iter()
+            .filter(|city| route.cross_track_km(city.latitude, city.longitude) <= options.corridor_km)
+            .collect();
+
+        pick_route(candidates, origin, options, |mut cities| {
+            cities.sort_by(|a, b| {
+                route.along_track(a.latitude, a.longitude).total_cmp(&route.along_track(b.latitude, b.longitude))
+            });
+            cities
+        })
+    }
+
+    /// The sentence describing the route from `city` on `bearing`.
+    ///
+    /// Always in English, like `location_text_for_point`; `route_cities`
+    /// gives the cities for describing the route in another language.
+    pub fn route_text(&self, city: &City, bearing: f64, options: &RouteOptions) -> String {
+        format!("If you fly on a bearing of {}° ({}) all the way around the globe, you will look down on {}.",
+            bearing,
+            geo::compass_point(bearing),
+            self.route_cities(city, bearing, options).iter().join(", "),
+        )
+    }
+
+    /// The most populous cities along the parallel through `origin`, heading
+    /// east.
+    pub fn latitude_cities<'a>(&'a self, origin: &'a City, options: &RouteOptions) -> Vec<&'a City> {
+        let candidates = self.same_latitude(origin.latitude, options.corridor_km);
+
+        pick_route(candidates, origin, options, |cities| sort_easterly(cities, origin.longitude))
+    }
+
+    /// The sentence describing the route along the parallel through `city`.
+    pub fn latitude_text(&self, city: &City, options: &TextOptions) -> String {
+        let mut context = city_context(city, options);
+        context.insert("route_east", route_value(&self.latitude_cities(city, &options.route), options));
+
+        options.templates().latitude.render(&context)
+    }
+
+    /// The most populous cities along the meridian through `origin`, heading
+    /// north over the pole and back round.
+    pub fn longitude_cities<'a>(&'a self, origin: &'a City, options: &RouteOptions) -> Vec<&'a City> {
+        let candidates = self.same_longitude(origin.longitude, options.corridor_km);
+
+        pick_route(candidates, origin, options, |cities| {
+            sort_northerly(cities, origin.latitude, origin.longitude, options.include_poles)
         })
-        .collect()
+    }
+
+    /// The sentence describing the route along the meridian through `city`.
+    pub fn longitude_text(&self, city: &City, options: &TextOptions) -> String {
+        let mut context = city_context(city, options);
+        context.insert("route_north", route_value(&self.longitude_cities(city, &options.route), options));
+
+        options.templates().longitude.render(&context)
+    }
+}
+
+impl From<Vec<City>> for Gazetteer {
+    fn from(cities: Vec<City>) -> Gazetteer {
+        Gazetteer::new(cities)
+    }
+}
+
+impl FromIterator<City> for Gazetteer {
+    fn from_iter<I: IntoIterator<Item = City>>(iter: I) -> Gazetteer {
+        Gazetteer::new(iter.into_iter().collect())
+    }
+}
+
+// The template variables describing a city itself.
+fn city_context(city: &City, options: &TextOptions) -> Context {
+    let locale = &options.locale;
+    Context::from([
+        ("city", Value::Text(display_name(city, options))),
+        ("province", Value::Text(spelled(&city.province, options))),
+        ("country", Value::Text(spelled(&city.country, options))),
+        ("flag", Value::Text(city.flag())),
+        ("lat_dms", Value::Text(locale.latitude_dms(city.latitude))),
+        ("lon_dms", Value::Text(locale.longitude_dms(city.longitude))),
+        ("lat", Value::Text(locale.format_number(city.latitude, 2))),
+        ("lon", Value::Text(locale.format_number(city.longitude, 2))),
+    ])
+}
+
+// The poles are named in the language of the text.
+fn route_value(cities: &[&City], options: &TextOptions) -> Value {
+    Value::List(cities
+        .iter()
+        .map(|city| HashMap::from([
+            ("name", if city.id() == NORTH_POLE.id() {
+                spelled(&options.locale.north_pole, options)
+            } else if city.id() == SOUTH_POLE.id() {
+                spelled(&options.locale.south_pole, options)
+            } else {
+                display_name(city, options)
+            }),
+            ("province", spelled(&city.province, options)),
+            ("country", spelled(&city.country, options)),
+            ("flag", city.flag()),
+        ]))
+        .collect())
+}
+
+// A city's name as it appears in the text, with its flag if asked for.
+fn display_name(city: &City, options: &TextOptions) -> String {
+    let name = match options.names {
+        NameStyle::Native => city.name.clone(),
+        NameStyle::Ascii if city.city_ascii.is_empty() => remove_diacritics(&city.name),
+        NameStyle::Ascii => city.city_ascii.clone(),
+    };
+    let flag = city.flag();
+    if options.flags && !flag.is_empty() {
+        format!("{} {}", name, flag)
+    } else {
+        name
+    }
+}
+
+// Provinces, countries and the poles have no ASCII spelling in the dataset, so
+// their diacritics are taken off instead.
+fn spelled(text: &str, options: &TextOptions) -> String {
+    match options.names {
+        NameStyle::Native => String::from(text),
+        NameStyle::Ascii => remove_diacritics(text),
+    }
+}
+
+// Splits accented letters into the letter and its accents and drops the
+// accents, so "Darién" becomes "Darien". Other characters are kept.
+fn remove_diacritics(text: &str) -> String {
+    text.nfd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+// Renders each hashtag and keeps only the letters, digits and underscores
+// Mastodon allows in one, so `{city}` for New York becomes `#NewYork`.
+fn hashtags(templates: &[Template], context: &Context) -> String {
+    templates
+        .iter()
+        .map(|template| template.render(context).chars().filter(|c| c.is_alphanumeric() || *c == '_').collect::<String>())
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| format!("#{}", tag))
+        .join(" ")
 }
 
 fn opposite_longitude(long: f64) -> f64 {
@@ -101,29 +962,53 @@ fn opposite_longitude(long: f64) -> f64 {
     opposite_long
 }
 
-const LONGITUDE_TOLERANCE: f64 = 0.5;
+/// The point on the exact opposite side of the Earth.
+pub fn antipode(lat: f64, long: f64) -> (f64, f64) {
+    (-lat, opposite_longitude(long))
+}
 
-fn same_longitude(long: f64) -> Vec<City> {
-    let opposite_long = opposite_longitude(long);
+// How many degrees of longitude span `km` at a given latitude; unbounded at
+// the poles, where every meridian meets.
+fn degrees_of_longitude(km: f64, latitude: f64) -> f64 {
+    let km_per_degree = geo::KM_PER_DEGREE * latitude.to_radians().cos();
+    if km_per_degree <= 0.0 { f64::INFINITY } else { km / km_per_degree }
+}
 
-    DATA
-        .iter()
-        .cloned()
-        .filter(|city| {
-            (city.longitude < long + LONGITUDE_TOLERANCE &&
-            long - LONGITUDE_TOLERANCE < city.longitude) ||
-            (city.longitude < opposite_long + LONGITUDE_TOLERANCE &&
-            opposite_long - LONGITUDE_TOLERANCE < city.longitude)
-        })
-        .collect()
+// The shorter way round between two longitudes, in degrees.
+fn longitude_difference(a: f64, b: f64) -> f64 {
+    ((a - b + 180.0).rem_euclid(360.0) - 180.0).abs()
 }
 
-fn top_by_population(mut cities: Vec<City>) -> Vec<City> {
+// Ranks the candidates and puts the winners in travel order. When the origin
+// is included it is taken out of the ranking first and added back at the end,
+// so it appears exactly once and never costs another city its place.
+fn pick_route<'a>(
+    candidates: Vec<&'a City>,
+    origin: &'a City,
+    options: &RouteOptions,
+    in_travel_order: impl FnOnce(Vec<&'a City>) -> Vec<&'a City>,
+) -> Vec<&'a City> {
+    let origin_id = origin.id();
+    let candidates = candidates
+        .into_iter()
+        .filter(|city| city.population >= options.min_population)
+        .filter(|city| !options.include_origin || city.id() != origin_id)
+        .collect();
+
+    let count = if options.include_origin { options.cities.saturating_sub(1) } else { options.cities };
+    let mut cities = in_travel_order(top_by_population(candidates, count));
+    if options.include_origin {
+        cities.push(origin);
+    }
+    cities
+}
+
+fn top_by_population(mut cities: Vec<&City>, count: usize) -> Vec<&City> {
     cities.sort_by(|a, b| b.population.partial_cmp(&a.population).unwrap());
-    cities.into_iter().take(NUM_CITIES).collect()
+    cities.into_iter().take(count).collect()
 }
 
-fn sort_easterly(mut cities: Vec<City>, start_long: f64) -> Vec<City> {
+fn sort_easterly(mut cities: Vec<&City>, start_long: f64) -> Vec<&City> {
     cities.sort_by(|a, b| a.longitude.partial_cmp(&b.longitude).unwrap());
 
     let (mut west, mut east): (Vec<_>, Vec<_>) = cities
@@ -139,7 +1024,7 @@ fn sort_easterly(mut cities: Vec<City>, start_long: f64) -> Vec<City> {
     east
 }
 
-fn sort_northerly(cities: Vec<City>, start_lat: f64, start_long: f64) -> Vec<City> {
+fn sort_northerly(cities: Vec<&City>, start_lat: f64, start_long: f64, include_poles: bool) -> Vec<&City> {
     let start_long_negative = start_long < 0.0;
 
     let (mut same_side, mut opp_side): (Vec<_>, Vec<_>) = cities
@@ -174,66 +1059,54 @@ fn sort_northerly(cities: Vec<City>, start_lat: f64, start_long: f64) -> Vec<Cit
 
     opp_side.sort_by(|a, b| b.latitude.partial_cmp(&a.latitude).unwrap());
 
-    north.push(NORTH_POLE.clone());
+    if include_poles {
+        north.push(&NORTH_POLE);
+    }
     north.append(&mut opp_side);
-    north.push(SOUTH_POLE.clone());
+    if include_poles {
+        north.push(&SOUTH_POLE);
+    }
     north.append(&mut south);
     north
 }
 
-fn latitude_cities(latitude: f64, longitude: f64) -> Vec<City> {
-    let cities = same_latitude(latitude);
-    let cities = top_by_population(cities);
-    let cities = sort_easterly(cities, longitude);
-    cities
-}
-
-fn latitude_text(city: &City) -> String {
-    let (latitude, longitude) = (city.latitude, city.longitude);
-    format!("If you fly along this latitude in an easterly direction, you will look down on {}, {}.", latitude_cities(latitude, longitude).iter().take(NUM_CITIES_LATITUDE).join(", "), city)
-}
-
-fn longitude_cities(latitude: f64, longitude: f64) -> Vec<City> {
-    let cities = same_longitude(longitude);
-    let cities = top_by_population(cities);
-    let cities = sort_northerly(cities, latitude, longitude);
-    cities
-}
-
-fn longitude_text(city: &City) -> String {
-    let (latitude, longitude) = (city.latitude, city.longitude);
-    format!("If you fly along this longitude starting north, you will look down on {}, {}.", longitude_cities(latitude, longitude).iter().take(NUM_CITIES_LONGITUDE).join(", "), city)
-}
-
-fn decimal_to_degrees_minutes(coord: f64) -> (f64, f64) {
-    (
-        coord.abs().floor(),
-        ((coord.abs() * 60.0) % 60.0).floor()
-    )
-}
-
 fn latitude_in_degrees(coord: f64) -> String {
-    let dir = if coord > 0.0 { "N" } else { "S" };
-    let (deg, min) = decimal_to_degrees_minutes(coord);
-    format!("{}°{}'{}", deg, min, dir)
+    locale::degrees_minutes(coord, if coord > 0.0 { "N" } else { "S" })
 }
 
 fn longitude_in_degrees(coord: f64) -> String {
-    let dir = if coord > 0.0 { "E" } else { "W" };
-    let (deg, min) = decimal_to_degrees_minutes(coord);
-    format!("{}°{}'{}", deg, min, dir)
+    locale::degrees_minutes(coord, if coord > 0.0 { "E" } else { "W" })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use assert_approx_eq::assert_approx_eq;
+    use template::Phrasebook;
+
+    lazy_static! {
+        static ref GAZETTEER: Gazetteer = Gazetteer::load().unwrap();
+    }
+
+    fn fixture_city(name: &str, latitude: f64, longitude: f64, population: f64) -> City {
+        City {
+            name: String::from(name),
+            city_ascii: remove_diacritics(name),
+            latitude,
+            longitude,
+            population,
+            country: String::from("Testland"),
+            iso2: String::new(),
+            iso3: String::new(),
+            province: String::from("Test Province"),
+        }
+    }
 
     #[test]
     fn it_finds_cities_with_same_latitude() {
         let lat = 40.4299986;
-        let cities = same_latitude(lat);
-        let mut names: Vec<_> = cities.iter().map(|ref c| &c.name).collect();
+        let cities = GAZETTEER.same_latitude(lat, DEFAULT_CORRIDOR_KM);
+        let mut names: Vec<_> = cities.iter().map(|c| &c.name).collect();
         names.sort();
         assert_eq!(
             names,
@@ -244,11 +1117,11 @@ mod tests {
     #[test]
     fn it_filters_to_ten_by_population() {
         let lat = 40.4299986;
-        let cities = same_latitude(lat);
+        let cities = GAZETTEER.same_latitude(lat, DEFAULT_CORRIDOR_KM);
 
-        let cities = top_by_population(cities);
+        let cities = top_by_population(cities, 10);
 
-        let names: Vec<_> = cities.iter().map(|ref c| &c.name).collect();
+        let names: Vec<_> = cities.iter().map(|c| &c.name).collect();
 
         assert_eq!(
             names,
@@ -260,12 +1133,12 @@ mod tests {
     fn it_sorts_easterly() {
         let lat = 40.4299986;
         let long = -79.99998539;
-        let cities = same_latitude(lat);
-        let cities = top_by_population(cities);
+        let cities = GAZETTEER.same_latitude(lat, DEFAULT_CORRIDOR_KM);
+        let cities = top_by_population(cities, 10);
 
         let cities = sort_easterly(cities, long);
 
-        let names: Vec<_> = cities.iter().map(|ref c| &c.name).collect();
+        let names: Vec<_> = cities.iter().map(|c| &c.name).collect();
 
         assert_eq!(
             names,
@@ -277,14 +1150,17 @@ mod tests {
     fn it_creates_latitude_text() {
         let city = City {
             name: String::from("Pittsburgh"),
+            city_ascii: String::from("Pittsburgh"),
             latitude: 40.4299986,
             longitude: -79.99998539,
             population: 0.0, // doesn't matter here
             country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
             province: String::from("Pennsylvania"),
         };
 
-        let latitude_text = latitude_text(&city);
+        let latitude_text = GAZETTEER.latitude_text(&city, &TextOptions::default());
 
         assert_eq!(
             latitude_text,
@@ -296,18 +1172,21 @@ mod tests {
     fn it_creates_latitude_text_for_small_cities() {
         let city = City {
             name: String::from("Jaque"),
+            city_ascii: String::from("Jaque"),
             latitude: 7.518958353,
             longitude: -78.16601465,
             population: 0.0, // doesn't matter here
             country: String::from("Panama"),
+            iso2: String::from("PA"),
+            iso3: String::from("PAN"),
             province: String::from("Darién"),
         };
 
-        let latitude_text = latitude_text(&city);
+        let latitude_text = GAZETTEER.latitude_text(&city, &TextOptions::default());
 
         assert_eq!(
             latitude_text,
-            "If you fly along this latitude in an easterly direction, you will look down on Bucaramanga, Cucuta, Bouake, Abeokuta, Ibadan, Oyo, Ife, Ado Ekiti, Ikare, Jaque."
+            "If you fly along this latitude in an easterly direction, you will look down on Bucaramanga, Cucuta, Bouake, Ibadan, Oyo, Ife, Ado Ekiti, Ikare, Davao, Jaque."
         );
     }
 
@@ -319,7 +1198,7 @@ mod tests {
             100.00001461
         );
 
-        let long = long * -1.0;
+        let long = -long;
         assert_approx_eq!(
             opposite_longitude(long),
             -100.00001461
@@ -329,27 +1208,27 @@ mod tests {
     #[test]
     fn it_finds_cities_with_same_longitude() {
         let long = -79.99998539;
-        let cities = same_longitude(long);
-        let mut names: Vec<_> = cities.iter().map(|ref c| &c.name).collect();
+        let cities = GAZETTEER.same_longitude(long, DEFAULT_CORRIDOR_KM);
+        let mut names: Vec<_> = cities.iter().map(|c| &c.name).collect();
         names.sort();
         assert_eq!(
             names,
-            vec!["Alor Setar", "Ang Thong", "Babahoyo", "Balboa", "Ban Houayxay", "Barrie", "Beaver Falls", "Blacksburg", "Bukittinggi", "Butterworth", "Chainat", "Charleston", "Chiang Rai", "Chiclayo", "Chitre", "Chone", "Chulucanas", "Cienfuegos", "Clarksburg", "Cobalt", "Colon", "Coral Gables", "Coral Springs", "Dali", "Erie", "Esmeraldas", "Ferrenafe", "Florence", "Fort Lauderdale", "Fort Pierce", "George Town", "Greensboro", "Guayaquil", "Hamilton", "Hat Yai", "Homestead", "Hua Hin", "Kamphaeng Phet", "Kanchanaburi", "Kangar", "Las Tablas", "Lijiang", "Macara", "Machala", "Miami", "Miami Beach", "Milagro", "Morgantown", "Moron", "Motupe", "Muisne", "Nakhon Pathom", "Nakhon Sawan", "Nakhon Si Thammarat", "New Liskeard", "Nonthaburi", "Olmos", "Orangeville", "Pacasmayo", "Padang", "Padangpanjang", "Panama City", "Parry Sound", "Penonome", "Phatthalung", "Phayao", "Phetchaburi", "Phichit", "Phitsanulok", "Phrae", "Pimentel", "Pinas", "Pittsburgh", "Placetas", "Portoviejo", "Prachuap Khiri Khan", "Ratchaburi", "Roanoke", "Sagua la Grande", "Salisbury", "Samut Sakhon", "Samut Songkhram", "Santa Clara", "Satun", "Sing Buri", "Sukhothai", "Sumter", "Sungai Petani", "Supham Buri", "Thung Song", "Trang", "Tumbes", "Tura", "Uthai Thani", "Uttaradit", "Vero Beach", "West Palm Beach", "White Sulphur Springs", "Winston-Salem", "Zhangye"]
+            vec!["Alor Setar", "Amundsen–Scott South Pole Station", "Ang Thong", "Babahoyo", "Balboa", "Ban Houayxay", "Barrie", "Beaver Falls", "Blacksburg", "Bukittinggi", "Butterworth", "Caibarien", "Chainat", "Charleston", "Chiang Rai", "Chiclayo", "Chitre", "Chone", "Chulucanas", "Cienfuegos", "Clarksburg", "Cobalt", "Colon", "Coral Gables", "Coral Springs", "Dali", "Danville", "Erie", "Esmeraldas", "Ferrenafe", "Florence", "Fort Lauderdale", "Fort Pierce", "George Town", "Greensboro", "Guayaquil", "Hall Beach", "Hamilton", "Hat Yai", "Homestead", "Hua Hin", "Kamphaeng Phet", "Kanchanaburi", "Kangar", "Kitchener", "Kodinskiy", "Lampang", "Las Tablas", "Lijiang", "Macara", "Machala", "Miami", "Miami Beach", "Milagro", "Moosonee", "Morgantown", "Moron", "Motupe", "Muisne", "Nakhon Pathom", "Nakhon Sawan", "Nakhon Si Thammarat", "New Liskeard", "Nonthaburi", "North Bay", "Olmos", "Orangeville", "Orillia", "Pacasmayo", "Padang", "Padangpanjang", "Panama City", "Parry Sound", "Penonome", "Phatthalung", "Phayao", "Phetchaburi", "Phichit", "Phitsanulok", "Phrae", "Pimentel", "Pinas", "Pittsburgh", "Placetas", "Portoviejo", "Prachuap Khiri Khan", "Ratchaburi", "Roanoke", "Sagua la Grande", "Salisbury", "Samut Sakhon", "Samut Songkhram", "Santa Clara", "Satun", "Sing Buri", "Sukhothai", "Sumter", "Sungai Petani", "Supham Buri", "Thung Song", "Toronto", "Trang", "Tulun", "Tumbes", "Tura", "Uthai Thani", "Uttaradit", "Vero Beach", "West Palm Beach", "White Sulphur Springs", "Winston-Salem", "Youngstown", "Zhangye"]
         );
     }
 
     #[test]
     fn it_filters_longitude_cities_to_ten_by_population() {
         let long = -79.99998539;
-        let cities = same_longitude(long);
+        let cities = GAZETTEER.same_longitude(long, DEFAULT_CORRIDOR_KM);
 
-        let cities = top_by_population(cities);
+        let cities = top_by_population(cities, 10);
 
-        let names: Vec<_> = cities.iter().map(|ref c| &c.name).collect();
+        let names: Vec<_> = cities.iter().map(|c| &c.name).collect();
 
         assert_eq!(
             names,
-            vec!["Miami", "Guayaquil", "George Town", "Pittsburgh", "Fort Lauderdale", "Padang", "Panama City", "West Palm Beach", "Hamilton", "Chiclayo"]
+            vec!["Toronto", "Miami", "Guayaquil", "George Town", "Pittsburgh", "Fort Lauderdale", "Padang", "Panama City", "West Palm Beach", "Hamilton"]
         );
     }
 
@@ -357,16 +1236,16 @@ mod tests {
     fn it_sorts_northerly() {
         let lat = 40.4299986;
         let long = -79.99998539;
-        let cities = same_longitude(long);
-        let cities = top_by_population(cities);
+        let cities = GAZETTEER.same_longitude(long, DEFAULT_CORRIDOR_KM);
+        let cities = top_by_population(cities, 10);
 
-        let cities = sort_northerly(cities, lat, long);
+        let cities = sort_northerly(cities, lat, long, true);
 
-        let names: Vec<_> = cities.iter().map(|ref c| &c.name).collect();
+        let names: Vec<_> = cities.iter().map(|c| &c.name).collect();
 
         assert_eq!(
             names,
-            vec!["Hamilton", "North Pole", "George Town", "Padang", "South Pole", "Chiclayo", "Guayaquil", "Panama City", "Miami", "Fort Lauderdale", "West Palm Beach", "Pittsburgh"]
+            vec!["Hamilton", "Toronto", "North Pole", "George Town", "Padang", "South Pole", "Guayaquil", "Panama City", "Miami", "Fort Lauderdale", "West Palm Beach", "Pittsburgh"]
         );
     }
 
@@ -374,18 +1253,21 @@ mod tests {
     fn it_creates_longitude_text() {
         let city = City {
             name: String::from("Pittsburgh"),
+            city_ascii: String::from("Pittsburgh"),
             latitude: 40.4299986,
             longitude: -79.99998539,
             population: 0.0, // doesn't matter here
             country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
             province: String::from("Pennsylvania"),
         };
 
-        let longitude_text = longitude_text(&city);
+        let longitude_text = GAZETTEER.longitude_text(&city, &TextOptions::default());
 
         assert_eq!(
             longitude_text,
-            "If you fly along this longitude starting north, you will look down on Hamilton, North Pole, George Town, Padang, South Pole, Chiclayo, Guayaquil, Panama City, Miami, Fort Lauderdale, West Palm Beach, Pittsburgh."
+            "If you fly along this longitude starting north, you will look down on Hamilton, Toronto, North Pole, George Town, Padang, South Pole, Guayaquil, Panama City, Miami, Fort Lauderdale, West Palm Beach, Pittsburgh."
         );
     }
 
@@ -393,18 +1275,21 @@ mod tests {
     fn it_creates_longitude_text_for_small_cities() {
         let city = City {
             name: String::from("Jaque"),
+            city_ascii: String::from("Jaque"),
             latitude: 7.518958353,
             longitude: -78.16601465,
             population: 0.0, // doesn't matter here
             country: String::from("Panama"),
+            iso2: String::from("PA"),
+            iso3: String::from("PAN"),
             province: String::from("Darién"),
         };
 
-        let longitude_text = longitude_text(&city);
+        let longitude_text = GAZETTEER.longitude_text(&city, &TextOptions::default());
 
         assert_eq!(
             longitude_text,
-            "If you fly along this longitude starting north, you will look down on Raleigh, North Pole, Xining, Panzhihua, Kota Baharu, Kuala Lumpur, Shah Alam, Kelang, Malacca, Pekanbaru, South Pole, Jaque."
+            "If you fly along this longitude starting north, you will look down on Raleigh, Rochester, North Pole, Xining, Kuala Lumpur, Shah Alam, Kelang, Malacca, Pekanbaru, South Pole, Quito, Jaque."
         );
     }
 
@@ -412,21 +1297,24 @@ mod tests {
     fn it_creates_full_text() {
         let city = City {
             name: String::from("Pittsburgh"),
+            city_ascii: String::from("Pittsburgh"),
             latitude: 40.4299986,
             longitude: -79.99998539,
             population: 0.0, // doesn't matter here
             country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
             province: String::from("Pennsylvania"),
         };
 
-        let text = location_text(&city);
+        let text = GAZETTEER.location_text(&city);
 
         assert_eq!(
             text,
 "You are now in Pittsburgh, Pennsylvania, United States of America
 40°25'N 79°59'W
 If you fly along this latitude in an easterly direction, you will look down on Philadelphia, New York, Madrid, Naples, Bursa, Baku, Hohhot, Datong, Jinxi, Pittsburgh.
-If you fly along this longitude starting north, you will look down on Hamilton, North Pole, George Town, Padang, South Pole, Chiclayo, Guayaquil, Panama City, Miami, Fort Lauderdale, West Palm Beach, Pittsburgh."
+If you fly along this longitude starting north, you will look down on Hamilton, Toronto, North Pole, George Town, Padang, South Pole, Guayaquil, Panama City, Miami, Fort Lauderdale, West Palm Beach, Pittsburgh."
         );
     }
 
@@ -434,21 +1322,702 @@ If you fly along this longitude starting north, you will look down on Hamilton,
     fn it_creates_full_text_for_small_cities() {
         let city = City {
             name: String::from("Jaque"),
+            city_ascii: String::from("Jaque"),
             latitude: 7.518958353,
             longitude: -78.16601465,
             population: 0.0, // doesn't matter here
             country: String::from("Panama"),
+            iso2: String::from("PA"),
+            iso3: String::from("PAN"),
             province: String::from("Darién"),
         };
 
-        let text = location_text(&city);
+        let text = GAZETTEER.location_text(&city);
 
         assert_eq!(
             text,
 "You are now in Jaque, Darién, Panama
 7°31'N 78°9'W
-If you fly along this latitude in an easterly direction, you will look down on Bucaramanga, Cucuta, Bouake, Abeokuta, Ibadan, Oyo, Ife, Ado Ekiti, Ikare, Jaque.
-If you fly along this longitude starting north, you will look down on Raleigh, North Pole, Xining, Panzhihua, Kota Baharu, Kuala Lumpur, Shah Alam, Kelang, Malacca, Pekanbaru, South Pole, Jaque."
+If you fly along this latitude in an easterly direction, you will look down on Bucaramanga, Cucuta, Bouake, Ibadan, Oyo, Ife, Ado Ekiti, Ikare, Davao, Jaque.
+If you fly along this longitude starting north, you will look down on Raleigh, Rochester, North Pole, Xining, Kuala Lumpur, Shah Alam, Kelang, Malacca, Pekanbaru, South Pole, Quito, Jaque."
+        );
+    }
+
+    #[test]
+    fn it_queries_a_fixture_gazetteer() {
+        let gazetteer: Gazetteer = vec![
+            fixture_city("Near", 10.2, 20.0, 100.0),
+            fixture_city("Far", 30.0, 20.3, 200.0),
+            fixture_city("Opposite", -5.0, -160.0, 300.0),
+        ].into_iter().collect();
+
+        let names: Vec<_> = gazetteer.same_latitude(10.0, DEFAULT_CORRIDOR_KM).iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, vec!["Near"]);
+
+        let mut names: Vec<_> = gazetteer.same_longitude(20.0, DEFAULT_CORRIDOR_KM).iter().map(|c| c.name.clone()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Far", "Near", "Opposite"]);
+    }
+
+    #[test]
+    fn it_has_no_random_location_when_empty() {
+        let gazetteer = Gazetteer::default();
+
+        assert!(gazetteer.is_empty());
+        assert_eq!(gazetteer.random_location(), None);
+    }
+
+    #[test]
+    fn it_finds_cities_within_a_radius() {
+        let gazetteer: Gazetteer = vec![
+            fixture_city("Origin", 0.0, 0.0, 100.0),
+            fixture_city("Close", 0.5, 0.0, 100.0),
+            fixture_city("Farther", 0.0, 1.5, 100.0),
+            fixture_city("Distant", 20.0, 20.0, 100.0),
+        ].into_iter().collect();
+
+        let names: Vec<_> = gazetteer.within_radius(0.0, 0.0, 200.0).iter().map(|(c, _)| c.name.clone()).collect();
+
+        assert_eq!(names, vec!["Origin", "Close", "Farther"]);
+    }
+
+    #[test]
+    fn it_finds_nearest_cities() {
+        let nearest = GAZETTEER.nearest(40.4299986, -79.99998539, 3);
+
+        assert_eq!(nearest.len(), 3);
+        assert_eq!(nearest[0].0.name, "Pittsburgh");
+        assert_approx_eq!(nearest[0].1, 0.0);
+        assert!(nearest[1].1 <= nearest[2].1);
+    }
+
+    #[test]
+    fn it_finds_nearest_cities_far_from_anywhere() {
+        let gazetteer: Gazetteer = vec![
+            fixture_city("Here", 0.0, 0.0, 100.0),
+            fixture_city("There", 0.0, 179.0, 100.0),
+        ].into_iter().collect();
+
+        let names: Vec<_> = gazetteer.nearest(-60.0, 100.0, 5).iter().map(|(c, _)| c.name.clone()).collect();
+
+        assert_eq!(names, vec!["There", "Here"]);
+    }
+
+    #[test]
+    fn it_creates_text_for_a_point() {
+        let text = GAZETTEER.location_text_for_point(40.5, -80.0).unwrap();
+
+        assert_eq!(
+            text,
+"You are near Pittsburgh, Pennsylvania, United States of America (8 km away)
+40°30'N 80°0'W"
+        );
+        assert_eq!(Gazetteer::default().location_text_for_point(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn it_orders_route_cities_along_the_great_circle() {
+        let gazetteer: Gazetteer = vec![
+            fixture_city("Origin", 0.0, 0.0, 100.0),
+            fixture_city("West", 0.0, -10.0, 100.0),
+            fixture_city("East", 0.2, 10.0, 100.0),
+            fixture_city("Off Route", 1.0, 50.0, 100.0),
+            fixture_city("Far East", -0.2, 170.0, 100.0),
+        ].into_iter().collect();
+
+        let origin = fixture_city("Origin", 0.0, 0.0, 100.0);
+        let options = RouteOptions { corridor_km: 55.0, include_origin: false, ..RouteOptions::default() };
+        let names: Vec<_> = gazetteer.route_cities(&origin, 90.0, &options).iter().map(|c| c.name.clone()).collect();
+
+        assert_eq!(names, vec!["East", "Far East", "West", "Origin"]);
+    }
+
+    #[test]
+    fn it_creates_route_text() {
+        let city = fixture_city("Origin", 0.0, 0.0, 100.0);
+        let gazetteer: Gazetteer = vec![
+            fixture_city("Northeast", 10.0, 10.05, 100.0),
+            fixture_city("Southwest", -10.0, -10.05, 100.0),
+        ].into_iter().collect();
+
+        assert_eq!(
+            gazetteer.route_text(&city, 45.0, &RouteOptions::default()),
+            "If you fly on a bearing of 45° (northeast) all the way around the globe, you will look down on Northeast, Southwest, Origin."
+        );
+    }
+
+    #[test]
+    fn it_finds_the_antipode() {
+        let (lat, long) = antipode(40.4299986, -79.99998539);
+
+        assert_approx_eq!(lat, -40.4299986);
+        assert_approx_eq!(long, 100.00001461);
+    }
+
+    #[test]
+    fn it_creates_full_text_with_antipode() {
+        let city = fixture_city("Origin", 40.0, -80.0, 100.0);
+        let gazetteer: Gazetteer = vec![
+            city.clone(),
+            fixture_city("Underneath", -39.5, 100.0, 100.0),
+        ].into_iter().collect();
+
+        let text = gazetteer.location_text_with(&city, &TextOptions { antipode: true, ..TextOptions::default() });
+
+        assert_eq!(
+            text.lines().last().unwrap(),
+            "Dig straight down and you'll come out near Underneath, Test Province, Testland (56 km from the exact opposite point)."
+        );
+        assert_eq!(gazetteer.location_text(&city).lines().count(), 4);
+    }
+
+    #[test]
+    fn it_words_the_antipode_for_the_locale() {
+        let city = fixture_city("Origin", 40.0, -80.0, 100.0);
+        let gazetteer: Gazetteer = vec![city.clone(), fixture_city("Underneath", -39.5, 100.0, 100.0)].into_iter().collect();
+        let options = TextOptions { locale: Locale::german(), phrasing: Phrasing::Short, ..TextOptions::default() };
+
+        assert_eq!(gazetteer.antipode_text(&city, &options).unwrap(), "Gegenüber: nahe Underneath, Testland (56 km).");
+        assert_eq!(Gazetteer::default().antipode_text(&city, &options), None);
+    }
+
+    #[test]
+    fn it_keeps_the_meridian_strip_the_same_width_on_the_ground() {
+        let gazetteer: Gazetteer = vec![
+            // One degree of longitude is about 111 km at the equator...
+            fixture_city("Equatorial", 0.0, 1.0, 100.0),
+            // ...but only about 38 km at 70°N.
+            fixture_city("Arctic", 70.0, 1.0, 100.0),
+            fixture_city("Pole", -89.99, 120.0, 100.0),
+        ].into_iter().collect();
+
+        let names: Vec<_> = gazetteer.same_longitude(0.0, 50.0).iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, vec!["Arctic", "Pole"]);
+
+        let names: Vec<_> = gazetteer.same_longitude(0.0, 120.0).iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, vec!["Equatorial", "Arctic", "Pole"]);
+    }
+
+    #[test]
+    fn it_uses_the_longitude_index_on_the_real_dataset() {
+        // The dataset reaches to within a few hundredths of a degree of the
+        // South Pole, which must not widen every lookup to the whole globe.
+        assert!(GAZETTEER.cities().iter().any(|city| city.latitude() < -89.9));
+
+        let candidates = GAZETTEER.meridian_candidates(-79.9959, DEFAULT_CORRIDOR_KM);
+
+        assert!(candidates.len() * 10 < GAZETTEER.cities().len(), "{} of {} cities scanned", candidates.len(), GAZETTEER.cities().len());
+    }
+
+    #[test]
+    fn it_wraps_the_meridian_strip_around_the_date_line() {
+        let gazetteer: Gazetteer = vec![
+            fixture_city("West of the Line", 10.0, 179.8, 100.0),
+            fixture_city("East of the Line", 10.0, -179.8, 100.0),
+        ].into_iter().collect();
+
+        let names: Vec<_> = gazetteer.same_longitude(-179.9, DEFAULT_CORRIDOR_KM).iter().map(|c| c.name.clone()).collect();
+
+        assert_eq!(names, vec!["West of the Line", "East of the Line"]);
+    }
+
+    #[test]
+    fn it_builds_routes_from_options() {
+        let origin = fixture_city("Origin", 0.0, 0.0, 50.0);
+        let gazetteer: Gazetteer = vec![
+            origin.clone(),
+            fixture_city("North", 60.0, 0.2, 300.0),
+            fixture_city("South", -60.0, 0.2, 200.0),
+            fixture_city("Tiny", 30.0, 0.1, 10.0),
+        ].into_iter().collect();
+        let names = |cities: Vec<&City>| cities.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+
+        let options = RouteOptions::default();
+        assert_eq!(names(gazetteer.longitude_cities(&origin, &options)), vec!["Tiny", "North", "North Pole", "South Pole", "South", "Origin"]);
+
+        let options = RouteOptions { cities: 2, include_poles: false, ..RouteOptions::default() };
+        assert_eq!(names(gazetteer.longitude_cities(&origin, &options)), vec!["North", "Origin"]);
+
+        let options = RouteOptions { include_origin: false, min_population: 100.0, ..RouteOptions::default() };
+        assert_eq!(names(gazetteer.longitude_cities(&origin, &options)), vec!["North", "North Pole", "South Pole", "South"]);
+    }
+
+    #[test]
+    fn it_names_a_large_origin_city_once_and_last() {
+        let city = City {
+            name: String::from("New York"),
+            city_ascii: String::from("New York"),
+            latitude: 40.74997906,
+            longitude: -73.98001693,
+            population: 0.0, // doesn't matter here
+            country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
+            province: String::from("New York"),
+        };
+
+        let latitude_text = GAZETTEER.latitude_text(&city, &TextOptions::default());
+
+        assert_eq!(
+            latitude_text,
+            "If you fly along this latitude in an easterly direction, you will look down on Madrid, Naples, Istanbul, Baku, Baotou, Hohhot, Jinxi, Anshan, Pittsburgh, New York."
+        );
+        assert_eq!(latitude_text.matches("New York").count(), 1);
+    }
+
+    #[test]
+    fn it_names_another_large_origin_city_once_and_last() {
+        let city = City {
+            name: String::from("Philadelphia"),
+            city_ascii: String::from("Philadelphia"),
+            latitude: 39.99997316,
+            longitude: -75.16999597,
+            population: 0.0, // doesn't matter here
+            country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
+            province: String::from("Pennsylvania"),
+        };
+
+        let latitude_text = GAZETTEER.latitude_text(&city, &TextOptions::default());
+
+        assert_eq!(
+            latitude_text,
+            "If you fly along this latitude in an easterly direction, you will look down on Madrid, Bursa, Ankara, Baku, Datong, Beijing, Tangshan, Denver, Pittsburgh, Philadelphia."
+        );
+        assert_eq!(latitude_text.matches("Philadelphia").count(), 1);
+    }
+
+    #[test]
+    fn it_only_leaves_out_the_origin_itself() {
+        let origin = fixture_city("Springfield", 40.0, -89.6, 100.0);
+        let gazetteer: Gazetteer = vec![
+            origin.clone(),
+            fixture_city("Springfield", 40.2, -83.8, 200.0),
+        ].into_iter().collect();
+
+        let names: Vec<_> = gazetteer.latitude_cities(&origin, &RouteOptions::default()).iter().map(|c| (c.name.clone(), c.longitude)).collect();
+
+        assert_eq!(names, vec![(String::from("Springfield"), -83.8), (String::from("Springfield"), -89.6)]);
+    }
+
+    #[test]
+    fn it_composes_text_that_fits_the_budget() {
+        let city = City {
+            name: String::from("Pittsburgh"),
+            city_ascii: String::from("Pittsburgh"),
+            latitude: 40.4299986,
+            longitude: -79.99998539,
+            population: 0.0, // doesn't matter here
+            country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
+            province: String::from("Pennsylvania"),
+        };
+        let options = TextOptions::default();
+
+        let full = GAZETTEER.compose_location(&city, &options, compose::DEFAULT_STATUS_LIMIT).unwrap();
+        assert_eq!(full, GAZETTEER.location_text(&city));
+
+        let fewer = GAZETTEER.compose_location(&city, &options, 400).unwrap();
+        assert!(compose::status_length(&fewer) <= 400);
+        assert!(fewer.starts_with("You are now in Pittsburgh"));
+        assert!(fewer.ends_with(", Pittsburgh."));
+
+        let short = GAZETTEER.compose_location(&city, &options, 200).unwrap();
+        assert_eq!(
+            short,
+"Pittsburgh, Pennsylvania, United States of America
+40°25'N 79°59'W
+East along this latitude: New York, Pittsburgh.
+North along this longitude: Toronto, North Pole, South Pole, Pittsburgh."
+        );
+    }
+
+    #[test]
+    fn it_shortens_a_locale_without_short_sentences_in_its_own_language() {
+        let city = GAZETTEER.find_city("Pittsburgh")[0].city;
+        let source = "[locale]\ncode = it\n[header]\nOra sei a {city}, {country}\n[latitude]\nVerso est: {route_east}.\n[longitude]\nVerso nord: {route_north}.\n";
+        let locale = Locale::parse(source, &Locale::english()).unwrap();
+        let options = TextOptions { locale, phrasing: Phrasing::Short, ..TextOptions::default() };
+
+        let text = GAZETTEER.compose_location(city, &options, 120).unwrap();
+
+        assert!(text.starts_with("Ora sei a Pittsburgh"), "{}", text);
+        assert!(text.contains("Verso est: ") && text.contains("Verso nord: "), "{}", text);
+        assert!(!text.contains("along this"), "{}", text);
+    }
+
+    #[test]
+    fn it_refuses_to_cut_names_to_fit() {
+        let city = fixture_city("Origin", 0.0, 0.0, 100.0);
+
+        let error = GAZETTEER.compose_location(&city, &TextOptions::default(), 20).unwrap_err();
+
+        assert_eq!(error.budget, 20);
+        assert!(error.shortest > 20);
+    }
+
+    #[test]
+    fn it_splits_long_text_into_a_thread() {
+        let city = City {
+            name: String::from("Pittsburgh"),
+            city_ascii: String::from("Pittsburgh"),
+            latitude: 40.4299986,
+            longitude: -79.99998539,
+            population: 0.0, // doesn't matter here
+            country: String::from("United States of America"),
+            iso2: String::from("US"),
+            iso3: String::from("USA"),
+            province: String::from("Pennsylvania"),
+        };
+        let options = TextOptions::default();
+
+        let whole = GAZETTEER.compose_thread(&city, &options, compose::DEFAULT_STATUS_LIMIT).unwrap();
+        assert_eq!(whole, vec![GAZETTEER.location_text(&city)]);
+
+        let thread = GAZETTEER.compose_thread(&city, &options, 250).unwrap();
+        assert_eq!(thread, GAZETTEER.location_parts(&city, &options));
+        assert_eq!(thread[0], "You are now in Pittsburgh, Pennsylvania, United States of America\n40°25'N 79°59'W");
+    }
+
+    #[test]
+    fn it_creates_text_from_a_custom_phrasebook() {
+        let city = fixture_city("Origin", 0.0, 0.0, 100.0);
+        let gazetteer: Gazetteer = vec![
+            city.clone(),
+            fixture_city("East", 0.0, 10.0, 100.0),
+            fixture_city("North", 10.0, 0.0, 100.0),
+        ].into_iter().collect();
+        let source = "[header]\nAhoy from {city} ({country})!\n[latitude]\nEastward: {#route_east \" → \"}{name}{/route_east}\n[longitude]\nNorthward: {route_north}";
+        let options = TextOptions {
+            locale: Locale { phrasebook: Phrasebook::parse(source, &Phrasebook::english()).unwrap(), ..Locale::english() },
+            ..TextOptions::default()
+        };
+
+        assert_eq!(
+            gazetteer.location_text_with(&city, &options),
+"Ahoy from Origin (Testland)!
+Eastward: East → Origin
+Northward: North, North Pole, South Pole, Origin"
+        );
+    }
+
+    #[test]
+    fn it_creates_text_in_another_language() {
+        let city = fixture_city("Origin", 40.5, -80.25, 100.0);
+        let gazetteer: Gazetteer = vec![
+            city.clone(),
+            fixture_city("East", 40.5, 10.0, 100.0),
+            fixture_city("North", 45.0, -80.25, 100.0),
+            fixture_city("Underneath", -40.0, 101.0, 100.0),
+        ].into_iter().collect();
+        let options = TextOptions { antipode: true, locale: Locale::german(), ..TextOptions::default() };
+
+        assert_eq!(
+            gazetteer.location_text_with(&city, &options),
+"Du bist jetzt in Origin, Test Province, Testland
+40°30'N 80°15'W
+Wenn du entlang dieses Breitengrads nach Osten fliegst, siehst du unter dir East, Origin.
+Wenn du entlang dieses Längengrads nach Norden fliegst, siehst du unter dir North, Nordpol, Südpol, Origin.
+Grab dich gerade nach unten durch und du kommst in der Nähe von Underneath, Test Province, Testland heraus (120 km vom genau gegenüberliegenden Punkt)."
+        );
+    }
+
+    #[test]
+    fn it_formats_decimal_coordinates_for_the_locale() {
+        let city = fixture_city("Origin", 40.5, -80.25, 100.0);
+        let gazetteer: Gazetteer = vec![city.clone()].into_iter().collect();
+        let source = "[header]\n{city} ({lat}, {lon})";
+        let locale = Locale::parse(source, &Locale::french()).unwrap();
+
+        let parts = gazetteer.location_parts(&city, &TextOptions { locale, ..TextOptions::default() });
+
+        assert_eq!(parts[0], "Origin (40,50, -80,25)");
+    }
+
+    #[test]
+    fn it_reports_a_location_as_data() {
+        let city = fixture_city("Origin", 0.0, 0.0, 100.0);
+        let gazetteer: Gazetteer = vec![
+            city.clone(),
+            fixture_city("East", 0.0, 10.0, 100.0),
+            fixture_city("North", 10.0, 0.0, 100.0),
+        ].into_iter().collect();
+
+        let location = gazetteer.location(&city, &TextOptions::default());
+
+        let names = |route: &[Place]| route.iter().map(|place| place.name.clone()).collect::<Vec<_>>();
+        assert_eq!(location.origin.name, "Origin");
+        assert_eq!(location.coordinates.lat_dms, "0°0'S");
+        assert_eq!(names(&location.latitude_route), vec!["East", "Origin"]);
+        assert_eq!(names(&location.longitude_route), vec!["North", "North Pole", "South Pole", "Origin"]);
+        let poles: Vec<_> = location.longitude_route.iter().map(|place| place.pole).collect();
+        assert_eq!(poles, vec![None, Some(report::Pole::North), Some(report::Pole::South), None]);
+        assert_eq!(location.antipode, None);
+
+        let json = serde_json::to_value(&location).unwrap();
+        assert_eq!(json["longitude_route"][1]["pole"], "north");
+        assert_eq!(json["latitude_route"][0]["name"], "East");
+        assert!(json["latitude_route"][0].get("pole").is_none());
+        assert!(json.get("antipode").is_none());
+    }
+
+    #[test]
+    fn it_makes_flags_from_country_codes() {
+        let city = City { iso2: String::from("us"), ..fixture_city("Origin", 0.0, 0.0, 100.0) };
+
+        assert_eq!(city.flag(), "🇺🇸");
+        assert_eq!(fixture_city("Origin", 0.0, 0.0, 100.0).flag(), "");
+        assert_eq!(City { iso2: String::from("U1"), ..city }.flag(), "");
+    }
+
+    #[test]
+    fn it_corrects_the_flags_the_dataset_gets_wrong() {
+        let dakar = GAZETTEER.find_city("Dakar")[0].city;
+        let options = TextOptions { flags: true, ..TextOptions::default() };
+
+        assert_eq!(dakar.flag(), "🇸🇳");
+        assert!(GAZETTEER.location_text_with(dakar, &options).starts_with("You are now in Dakar 🇸🇳,"));
+        assert_eq!(GAZETTEER.find_city("Pristina")[0].city.flag(), "");
+    }
+
+    #[test]
+    fn it_adds_flags_and_hashtags() {
+        let city = City { name: String::from("New York"), iso2: String::from("US"), ..fixture_city("Origin", 0.0, 0.0, 100.0) };
+        let gazetteer: Gazetteer = vec![
+            city.clone(),
+            City { iso2: String::from("ES"), ..fixture_city("East", 0.0, 10.0, 100.0) },
+            fixture_city("North", 10.0, 0.0, 100.0),
+        ].into_iter().collect();
+        let options = TextOptions {
+            flags: true,
+            hashtags: template::hashtags("#Geography {city} {country}").unwrap(),
+            ..TextOptions::default()
+        };
+
+        assert_eq!(
+            gazetteer.location_text_with(&city, &options),
+"You are now in New York 🇺🇸, Test Province, Testland
+0°0'S 0°0'W
+If you fly along this latitude in an easterly direction, you will look down on East 🇪🇸, New York 🇺🇸.
+If you fly along this longitude starting north, you will look down on North, North Pole, South Pole, New York 🇺🇸.
+#Geography #NewYork #Testland"
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn it_finds_cities_by_either_spelling() {
+        let native: Vec<_> = GAZETTEER.cities_named("Tômbua").iter().map(|c| c.name()).collect();
+        let ascii: Vec<_> = GAZETTEER.cities_named("tombua").iter().map(|c| c.name()).collect();
+
+        assert_eq!(native, vec!["Tômbua"]);
+        assert_eq!(ascii, native);
+        assert!(GAZETTEER.cities_named("Tombuaa").is_empty());
+    }
+
+    #[test]
+    fn it_writes_names_without_diacritics() {
+        let city = City {
+            city_ascii: String::from("Tombua"),
+            province: String::from("Darién"),
+            country: String::from("Panamá"),
+            ..fixture_city("Tômbua", 0.0, 0.0, 100.0)
+        };
+        let gazetteer: Gazetteer = vec![
+            city.clone(),
+            fixture_city("Zürich", 0.0, 10.0, 100.0),
+        ].into_iter().collect();
+        let options = TextOptions { names: NameStyle::Ascii, locale: Locale::german(), ..TextOptions::default() };
+
+        let parts = gazetteer.location_parts(&city, &options);
+
+        assert_eq!(parts[0], "Du bist jetzt in Tombua, Darien, Panama\n0°0'S 0°0'W");
+        assert_eq!(parts[1], "Wenn du entlang dieses Breitengrads nach Osten fliegst, siehst du unter dir Zurich, Tombua.");
+        assert!(parts[2].contains("Nordpol, Sudpol, Tombua."));
+    }
+
+    #[test]
+    fn it_finds_a_city_by_name() {
+        let found: Vec<_> = GAZETTEER.find_city("paris").iter().map(|m| (m.city.name(), m.distance)).collect();
+
+        assert_eq!(found[0], ("Paris", 0));
+        assert_eq!(GAZETTEER.find_city("SAO PAULO")[0].city.name(), "Sao Paulo");
+        assert_eq!(GAZETTEER.find_city("zurich")[0].city.name(), "Zürich");
+    }
+
+    #[test]
+    fn it_forgives_typos() {
+        let found = GAZETTEER.find_city("Pitsburg");
+
+        assert_eq!(found[0].city.name(), "Pittsburgh");
+        assert_eq!(found[0].distance, 2);
+        assert!(GAZETTEER.find_city("Xyzzyplugh").is_empty());
+    }
+
+    #[test]
+    fn it_ranks_same_name_cities_by_population() {
+        let provinces: Vec<_> = GAZETTEER.find_city("Springfield").iter().map(|m| m.city.province.as_str()).collect();
+
+        assert_eq!(provinces, vec!["Massachusetts", "Missouri", "Illinois", "Ohio", "Oregon"]);
+    }
+
+    #[test]
+    fn it_disambiguates_by_province_or_country() {
+        let found = GAZETTEER.find_city("Springfield, Illinois");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].city.province, "Illinois");
+
+        let found = GAZETTEER.find_city("Portland, Australia");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].city.province, "Victoria");
+
+        assert_eq!(GAZETTEER.find_city("Portland, US, Maine")[0].city.province, "Maine");
+        assert!(GAZETTEER.find_city("Springfield, Texas").is_empty());
+    }
+
+    #[test]
+    fn it_builds_a_city() {
+        let city = City::new("Darién", 8.4, -77.9, 1500.0, "Panama", "Darién").unwrap().with_iso_codes("PA", "PAN");
+
+        assert_eq!(city.name(), "Darién");
+        assert_eq!(city.city_ascii(), "Darien");
+        assert_eq!(city.latitude(), 8.4);
+        assert_eq!(city.longitude(), -77.9);
+        assert_eq!(city.population(), 1500.0);
+        assert_eq!(city.country(), "Panama");
+        assert_eq!(city.province(), "Darién");
+        assert_eq!(city.flag(), "🇵🇦");
+        assert_eq!(city.with_city_ascii("Darien City").city_ascii(), "Darien City");
+        // Each text can be a different kind of string.
+        assert!(City::new(String::from("Darién"), 8.4, -77.9, 1500.0, "Panama", String::from("Darién")).is_ok());
+    }
+
+    #[test]
+    fn it_rejects_impossible_cities() {
+        assert_eq!(City::new("Nowhere", 90.5, 0.0, 0.0, "", "").unwrap_err(), CityError::Latitude(90.5));
+        assert_eq!(City::new("Nowhere", 0.0, -180.5, 0.0, "", "").unwrap_err(), CityError::Longitude(-180.5));
+        assert_eq!(City::new("Nowhere", 0.0, 0.0, -1.0, "", "").unwrap_err(), CityError::Population(-1.0));
+        assert!(City::new("Nowhere", f64::NAN, 0.0, 0.0, "", "").is_err());
+        assert!(City::new("Edge", -90.0, 180.0, 0.0, "", "").is_ok());
+    }
+
+    #[test]
+    fn it_compares_cities_by_id() {
+        let city = City::new("Pittsburgh", 40.4299986, -79.99998539, 1535267.5, "United States of America", "Pennsylvania").unwrap();
+        let renamed = City::new("Pittsburg", 40.4299986, -79.99998539, 1535267.5, "United States of America", "Pennsylvania").unwrap();
+        let recounted = City::new("Pittsburgh", 40.4299986, -79.99998539, 1.0, "United States of America", "Pennsylvania").unwrap();
+
+        assert_eq!(city.id(), recounted.id());
+        assert_eq!(city, recounted);
+        assert_ne!(city, renamed);
+        assert_eq!(city.id().to_string().len(), 16);
+        assert_eq!(std::collections::HashSet::from([city.clone(), recounted]).len(), 1);
+        // The id must never change between releases, or posting history breaks.
+        assert_eq!(city.id(), City::new("Pittsburgh", 40.4299986, -79.99998539, 0.0, "United States of America", "Pennsylvania").unwrap().id());
+    }
+
+    #[test]
+    fn it_writes_city_ids_as_hex() {
+        let id = City::new("Pittsburgh", 40.43, -80.0, 1535267.5, "United States of America", "Pennsylvania").unwrap().id();
+
+        let json = serde_json::to_string(&id).unwrap();
+
+        assert_eq!(json, format!("\"{}\"", id));
+        assert_eq!(serde_json::from_str::<CityId>(&json).unwrap(), id);
+        assert!(serde_json::from_str::<CityId>("\"Pittsburgh\"").is_err());
+    }
+
+    #[test]
+    fn it_serializes_a_city() {
+        let city = City::new("Pittsburgh", 40.43, -80.0, 1535267.5, "United States of America", "Pennsylvania").unwrap()
+            .with_iso_codes("US", "USA");
+
+        let json = serde_json::to_value(&city).unwrap();
+
+        assert_eq!(json["name"], "Pittsburgh");
+        assert_eq!(json["latitude"], 40.43);
+        assert_eq!(json["iso2"], "US");
+    }
+
+    #[test]
+    fn it_looks_cities_up_by_id() {
+        let city = fixture_city("Origin", 0.0, 0.0, 100.0);
+        let gazetteer: Gazetteer = vec![city.clone(), fixture_city("East", 0.0, 10.0, 100.0)].into_iter().collect();
+
+        assert_eq!(gazetteer.city(city.id()).map(City::name), Some("Origin"));
+        assert_eq!(gazetteer.city(fixture_city("Elsewhere", 1.0, 1.0, 0.0).id()), None);
+    }
+
+    #[test]
+    fn it_tells_namesakes_in_other_provinces_apart() {
+        let origin = fixture_city("Springfield", 40.0, -89.6, 100.0);
+        let namesake = City { province: String::from("Other Province"), ..origin.clone() };
+        let gazetteer: Gazetteer = vec![origin.clone(), namesake].into_iter().collect();
+
+        let provinces: Vec<_> = gazetteer.latitude_cities(&origin, &RouteOptions::default()).iter().map(|c| c.province.clone()).collect();
+
+        assert_eq!(provinces, vec!["Other Province", "Test Province"]);
+    }
+
+    #[test]
+    fn it_repeats_the_same_city_for_the_same_seed() {
+        let first = GAZETTEER.seeded_city(20_241_231).unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(GAZETTEER.seeded_city(20_241_231).unwrap(), first);
+        }
+        let others: std::collections::HashSet<_> = (0..20).map(|seed| GAZETTEER.seeded_city(seed).unwrap().id()).collect();
+        assert!(others.len() > 1);
+    }
+
+    #[test]
+    fn it_takes_an_injected_generator() {
+        let options = TextOptions::default();
+        let mut rng = seed::rng(Some(7));
+
+        let text = GAZETTEER.random_location_with_rng(&options, &mut rng).unwrap();
+
+        assert_eq!(text, GAZETTEER.location_text_with(GAZETTEER.seeded_city(7).unwrap(), &options));
+        assert_eq!(Gazetteer::default().random_location_with_rng(&options, &mut rng), None);
+    }
+
+    #[test]
+    fn it_selects_cities_by_strategy() {
+        let selection = Selection {
+            weighting: selection::Weighting::Population,
+            stratify: selection::Stratify::Continent,
+            min_population: 1_000_000.0,
+        };
+        let mut rng = seed::rng(Some(3));
+
+        for _ in 0..20 {
+            assert!(GAZETTEER.select_city(&selection, &mut rng).unwrap().population() >= 1_000_000.0);
+        }
+        let first = GAZETTEER.select_city(&selection, &mut seed::rng(Some(3)));
+        assert_eq!(first, GAZETTEER.select_city(&selection, &mut seed::rng(Some(3))));
+    }
+
+    #[test]
+    fn it_seeds_the_same_city_as_the_default_selection() {
+        for seed in 0..10 {
+            assert_eq!(GAZETTEER.seeded_city(seed), GAZETTEER.select_city(&Selection::default(), &mut seed::rng(Some(seed))));
+        }
+    }
+
+    #[test]
+    fn it_tours_every_city_before_repeating_one() {
+        let selection = Selection { min_population: 10_000_000.0, ..Selection::default() };
+        let eligible = GAZETTEER.cities().iter().filter(|city| city.population() >= 10_000_000.0).count();
+        let now = chrono::Utc::now();
+        let mut history = History::new();
+        let mut rng = seed::rng(Some(5));
+
+        for _ in 0..eligible {
+            let city = GAZETTEER.select_fresh_city(&selection, &history, Repeats::Tour, now, &mut rng).unwrap();
+            assert!(history.entries().iter().all(|entry| entry.city != city.id()), "{} came up twice", city.name());
+            history.record(history::Entry { city: city.id(), name: city.name().to_string(), posted_at: now, status_id: None }).unwrap();
+        }
+        assert!(GAZETTEER.select_fresh_city(&selection, &history, Repeats::Tour, now, &mut rng).is_some());
+    }
+}
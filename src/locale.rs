@@ -0,0 +1,327 @@
+// Everything that changes with the language of a location text: the
+// sentences, the compass letters, how numbers are written and the names of the
+// poles.
+
+use std::path::Path;
+use std::fs;
+
+use super::template::{Phrasebook, Template, TemplateError, Templates};
+
+/// Codes of the locales built into the crate.
+pub const BUILT_IN: &[&str] = &["en", "de", "fr", "es"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Locale {
+    /// ISO 639-1 language code, also sent as the language of posted statuses.
+    pub code: String,
+    pub phrasebook: Phrasebook,
+    /// Letters for north, south, east and west, in that order.
+    pub compass: [String; 4],
+    pub decimal_separator: String,
+    /// Put between groups of three digits; empty for no grouping.
+    pub thousands_separator: String,
+    pub north_pole: String,
+    pub south_pole: String,
+}
+
+impl Locale {
+    pub fn english() -> Locale {
+        Locale {
+            code: String::from("en"),
+            phrasebook: Phrasebook::english(),
+            compass: compass(["N", "S", "E", "W"]),
+            decimal_separator: String::from("."),
+            thousands_separator: String::from(","),
+            north_pole: String::from("North Pole"),
+            south_pole: String::from("South Pole"),
+        }
+    }
+
+    pub fn german() -> Locale {
+        Locale {
+            code: String::from("de"),
+            phrasebook: phrasebook([
+                "Du bist jetzt in {city}, {province}, {country}\n{lat_dms} {lon_dms}",
+                "Wenn du entlang dieses Breitengrads nach Osten fliegst, siehst du unter dir {route_east}.",
+                "Wenn du entlang dieses Längengrads nach Norden fliegst, siehst du unter dir {route_north}.",
+                "Grab dich gerade nach unten durch und du kommst in der Nähe von {antipode_city}, {antipode_province}, {antipode_country} heraus ({antipode_km} km vom genau gegenüberliegenden Punkt).",
+            ], [
+                "{city}, {province}, {country}\n{lat_dms} {lon_dms}",
+                "Nach Osten entlang des Breitengrads: {route_east}.",
+                "Nach Norden entlang des Längengrads: {route_north}.",
+                "Gegenüber: nahe {antipode_city}, {antipode_country} ({antipode_km} km).",
+            ]),
+            compass: compass(["N", "S", "O", "W"]),
+            decimal_separator: String::from(","),
+            thousands_separator: String::from("."),
+            north_pole: String::from("Nordpol"),
+            south_pole: String::from("Südpol"),
+        }
+    }
+
+    pub fn french() -> Locale {
+        Locale {
+            code: String::from("fr"),
+            phrasebook: phrasebook([
+                "Vous êtes maintenant à {city}, {province}, {country}\n{lat_dms} {lon_dms}",
+                "Si vous volez vers l'est le long de cette latitude, vous survolerez {route_east}.",
+                "Si vous volez vers le nord le long de cette longitude, vous survolerez {route_north}.",
+                "Creusez tout droit et vous ressortirez près de {antipode_city}, {antipode_province}, {antipode_country} (à {antipode_km} km du point exactement opposé).",
+            ], [
+                "{city}, {province}, {country}\n{lat_dms} {lon_dms}",
+                "Vers l'est sur cette latitude : {route_east}.",
+                "Vers le nord sur cette longitude : {route_north}.",
+                "À l'opposé : près de {antipode_city}, {antipode_country} ({antipode_km} km).",
+            ]),
+            compass: compass(["N", "S", "E", "O"]),
+            decimal_separator: String::from(","),
+            // A narrow no-break space, so numbers are never split across lines.
+            thousands_separator: String::from("\u{202f}"),
+            north_pole: String::from("pôle Nord"),
+            south_pole: String::from("pôle Sud"),
+        }
+    }
+
+    pub fn spanish() -> Locale {
+        Locale {
+            code: String::from("es"),
+            phrasebook: phrasebook([
+                "Ahora estás en {city}, {province}, {country}\n{lat_dms} {lon_dms}",
+                "Si vuelas hacia el este a lo largo de esta latitud, verás desde arriba {route_east}.",
+                "Si vuelas hacia el norte a lo largo de esta longitud, verás desde arriba {route_north}.",
+                "Cava en línea recta y saldrás cerca de {antipode_city}, {antipode_province}, {antipode_country} (a {antipode_km} km del punto exactamente opuesto).",
+            ], [
+                "{city}, {province}, {country}\n{lat_dms} {lon_dms}",
+                "Hacia el este por esta latitud: {route_east}.",
+                "Hacia el norte por esta longitud: {route_north}.",
+                "Al otro lado: cerca de {antipode_city}, {antipode_country} ({antipode_km} km).",
+            ]),
+            compass: compass(["N", "S", "E", "O"]),
+            decimal_separator: String::from(","),
+            thousands_separator: String::from("."),
+            north_pole: String::from("Polo Norte"),
+            south_pole: String::from("Polo Sur"),
+        }
+    }
+
+    /// The built-in locale for a language code such as `de`, if there is one.
+    pub fn built_in(code: &str) -> Option<Locale> {
+        match code.trim().to_lowercase().as_str() {
+            "en" => Some(Locale::english()),
+            "de" => Some(Locale::german()),
+            "fr" => Some(Locale::french()),
+            "es" => Some(Locale::spanish()),
+            _ => None,
+        }
+    }
+
+    /// Reads a locale file, keeping `base` for anything it leaves out.
+    ///
+    /// A locale file is a phrasebook file (see `Phrasebook::parse`) with an
+    /// optional `[locale]` section of `key = value` lines: `code`, `compass`
+    /// (four letters for north, south, east and west separated by spaces),
+    /// `decimal`, `thousands`, `north pole` and `south pole`.
+    pub fn parse(source: &str, base: &Locale) -> Result<Locale, TemplateError> {
+        let mut locale = base.clone();
+        let mut settings = Vec::new();
+        let mut sentences = Vec::new();
+        let mut in_settings = false;
+
+        for line in source.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                in_settings = trimmed[1..trimmed.len() - 1].trim() == "locale";
+                if in_settings {
+                    continue;
+                }
+            }
+            if in_settings {
+                settings.push(trimmed);
+            } else {
+                sentences.push(line);
+            }
+        }
+
+        for setting in settings {
+            if setting.is_empty() || setting.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match setting.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return error(format!("in [locale]: `{}` is not a `key = value` line", setting)),
+            };
+            match key {
+                "code" => locale.code = String::from(value),
+                "compass" => {
+                    let letters: Vec<&str> = value.split_whitespace().collect();
+                    match letters[..] {
+                        [north, south, east, west] => locale.compass = compass([north, south, east, west]),
+                        _ => return error(format!("in [locale]: compass needs four letters, not `{}`", value)),
+                    }
+                }
+                "decimal" => locale.decimal_separator = String::from(value),
+                "thousands" => locale.thousands_separator = String::from(value),
+                "north pole" => locale.north_pole = String::from(value),
+                "south pole" => locale.south_pole = String::from(value),
+                _ => return error(format!("in [locale]: unknown setting `{}`", key)),
+            }
+        }
+
+        locale.phrasebook = Phrasebook::parse(&sentences.join("\n"), &base.phrasebook)?;
+        Ok(locale)
+    }
+
+    /// Loads a locale file on top of English.
+    pub fn load_from_path<P: AsRef<Path>>(path: P) -> Result<Locale, TemplateError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| TemplateError { message: format!("failed to read {}: {}", path.display(), e) })?;
+
+        Locale::parse(&source, &Locale::english())
+            .map_err(|e| TemplateError { message: format!("{} {}", path.display(), e.message) })
+    }
+
+    /// A built-in locale by its code, otherwise a locale file at that path.
+    pub fn load(code_or_path: &str) -> Result<Locale, TemplateError> {
+        match Locale::built_in(code_or_path) {
+            Some(locale) => Ok(locale),
+            None => Locale::load_from_path(code_or_path),
+        }
+    }
+
+    /// Writes `value` with `decimals` digits after the separator, grouping the
+    /// whole part in thousands.
+    pub fn format_number(&self, value: f64, decimals: usize) -> String {
+        let digits = format!("{:.*}", decimals, value.abs());
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (digits.as_str(), None),
+        };
+
+        let mut out = String::new();
+        if value < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        for (index, digit) in whole.chars().enumerate() {
+            if index > 0 && (whole.len() - index) % 3 == 0 {
+                out.push_str(&self.thousands_separator);
+            }
+            out.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            out.push_str(&self.decimal_separator);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// A latitude in degrees and minutes, such as `40°25'N`.
+    pub fn latitude_dms(&self, coord: f64) -> String {
+        let direction = if coord > 0.0 { &self.compass[0] } else { &self.compass[1] };
+        degrees_minutes(coord, direction)
+    }
+
+    /// A longitude in degrees and minutes, such as `79°59'W`.
+    pub fn longitude_dms(&self, coord: f64) -> String {
+        let direction = if coord > 0.0 { &self.compass[2] } else { &self.compass[3] };
+        degrees_minutes(coord, direction)
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Locale {
+        Locale::english()
+    }
+}
+
+pub(crate) fn degrees_minutes(coord: f64, direction: &str) -> String {
+    let degrees = coord.abs().floor();
+    let minutes = ((coord.abs() * 60.0) % 60.0).floor();
+    format!("{}°{}'{}", degrees, minutes, direction)
+}
+
+fn error<T>(message: String) -> Result<T, TemplateError> {
+    Err(TemplateError { message })
+}
+
+fn compass(letters: [&str; 4]) -> [String; 4] {
+    letters.map(String::from)
+}
+
+// Builds a phrasebook from the header, latitude, longitude and antipode
+// sentences of the full and short wording.
+fn phrasebook(full: [&str; 4], short: [&str; 4]) -> Phrasebook {
+    let templates = |[header, latitude, longitude, antipode]: [&str; 4]| Templates {
+        header: Template::parse(header).unwrap(),
+        latitude: Template::parse(latitude).unwrap(),
+        longitude: Template::parse(longitude).unwrap(),
+        antipode: Template::parse(antipode).unwrap(),
+    };
+
+    Phrasebook { full: templates(full), short: templates(short) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{FIELDS, LISTS, SCALARS};
+
+    #[test]
+    fn it_has_every_built_in_locale() {
+        for code in BUILT_IN {
+            let locale = Locale::built_in(code).unwrap();
+
+            assert_eq!(&locale.code, code);
+            for templates in [&locale.phrasebook.full, &locale.phrasebook.short] {
+                for template in [&templates.header, &templates.latitude, &templates.longitude, &templates.antipode] {
+                    assert!(template.check(SCALARS, LISTS, FIELDS).is_ok());
+                }
+            }
+        }
+        assert_eq!(Locale::built_in("xx"), None);
+    }
+
+    #[test]
+    fn it_formats_numbers() {
+        assert_eq!(Locale::english().format_number(12345.678, 2), "12,345.68");
+        assert_eq!(Locale::german().format_number(12345.678, 2), "12.345,68");
+        assert_eq!(Locale::french().format_number(1234.0, 0), "1\u{202f}234");
+        assert_eq!(Locale::spanish().format_number(-0.004, 2), "0,00");
+        assert_eq!(Locale::spanish().format_number(-79.99998539, 2), "-80,00");
+        assert_eq!(Locale::english().format_number(999.0, 0), "999");
+    }
+
+    #[test]
+    fn it_formats_coordinates_with_local_compass_letters() {
+        assert_eq!(Locale::english().longitude_dms(100.5), "100°30'E");
+        assert_eq!(Locale::german().longitude_dms(100.5), "100°30'O");
+        assert_eq!(Locale::french().longitude_dms(-79.99998539), "79°59'O");
+        assert_eq!(Locale::spanish().latitude_dms(-33.5), "33°30'S");
+    }
+
+    #[test]
+    fn it_reads_a_locale_file() {
+        let source = "[locale]\ncode = it\ncompass = N S E O\ndecimal = ,\nthousands = .\nnorth pole = Polo Nord\n[header]\nOra sei a {city}\n";
+
+        let locale = Locale::parse(source, &Locale::english()).unwrap();
+
+        assert_eq!(locale.code, "it");
+        assert_eq!(locale.longitude_dms(-10.0), "10°0'O");
+        assert_eq!(locale.format_number(1234.5, 1), "1.234,5");
+        assert_eq!(locale.north_pole, "Polo Nord");
+        assert_eq!(locale.south_pole, "South Pole");
+        assert_eq!(locale.phrasebook.full.header, Template::parse("Ora sei a {city}").unwrap());
+        assert_eq!(locale.phrasebook.full.latitude, Phrasebook::english().full.latitude);
+        assert_eq!(locale.phrasebook.short.header, Template::parse("Ora sei a {city}").unwrap());
+    }
+
+    #[test]
+    fn it_rejects_bad_locale_files() {
+        let base = Locale::english();
+
+        assert!(Locale::parse("[locale]\ncompass = N S", &base).is_err());
+        assert!(Locale::parse("[locale]\ncolour = red", &base).is_err());
+        assert!(Locale::parse("[locale]\njust words", &base).is_err());
+        assert!(Locale::parse("[footer]\nBye", &base).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+// Structured form of a location text, for consumers that want the data rather
+// than the prose.
+
+use serde::Serialize;
+
+use super::{City, CityId, NORTH_POLE, SOUTH_POLE};
+
+/// Everything a location text says, ready to serialize.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Location {
+    pub origin: Place,
+    pub coordinates: Coordinates,
+    /// Cities along the parallel, heading east, ending with the origin.
+    pub latitude_route: Vec<Place>,
+    /// Cities along the meridian, heading north over the pole and back round,
+    /// ending with the origin.
+    pub longitude_route: Vec<Place>,
+    /// Set when the antipode was asked for and a city was found near it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub antipode: Option<Antipode>,
+}
+
+/// A city, or a pole, named in a location.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Place {
+    pub id: CityId,
+    pub name: String,
+    pub city_ascii: String,
+    pub province: String,
+    pub country: String,
+    pub iso2: String,
+    pub iso3: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub population: f64,
+    /// Which pole this is, if it is one rather than a city.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pole: Option<Pole>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Pole {
+    North,
+    South,
+}
+
+/// The origin's position, as numbers and written out for the locale.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub lat_dms: String,
+    pub lon_dms: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Antipode {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The city closest to the antipode.
+    pub nearest: Place,
+    /// How far that city is from the antipode.
+    pub distance_km: f64,
+}
+
+impl From<&City> for Place {
+    fn from(city: &City) -> Place {
+        let pole = if city.id() == NORTH_POLE.id() {
+            Some(Pole::North)
+        } else if city.id() == SOUTH_POLE.id() {
+            Some(Pole::South)
+        } else {
+            None
+        };
+
+        Place {
+            id: city.id(),
+            name: city.name.clone(),
+            city_ascii: city.city_ascii.clone(),
+            province: city.province.clone(),
+            country: city.country.clone(),
+            iso2: city.iso2.clone(),
+            iso3: city.iso3.clone(),
+            latitude: city.latitude,
+            longitude: city.longitude,
+            population: city.population,
+            pole,
+        }
+    }
+}
@@ -0,0 +1,361 @@
+// When `toot daemon` posts: either at a fixed interval or whenever a cron
+// expression matches, optionally shifted by a random delay so posts do not
+// land on the exact same minute every time.
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use rand::Rng;
+use std::str::FromStr;
+use std::{error, fmt};
+
+use super::cli;
+
+#[derive(Debug, PartialEq)]
+pub struct ScheduleError {
+    pub message: String,
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid schedule: {}", self.message)
+    }
+}
+
+impl error::Error for ScheduleError {}
+
+fn invalid(message: String) -> ScheduleError {
+    ScheduleError { message }
+}
+
+/// The times a schedule is due, before any jitter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Timing {
+    Every(Duration),
+    Cron(Cron),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub timing: Timing,
+    /// Each post is delayed by a random amount up to this long.
+    pub jitter: Duration,
+}
+
+/// What to do about slots that went by while the daemon was not running.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Missed {
+    /// Wait for the next slot.
+    #[default]
+    Skip,
+    /// Post once straight away, however many slots were missed.
+    CatchUp,
+}
+
+impl FromStr for Missed {
+    type Err = ScheduleError;
+
+    fn from_str(value: &str) -> Result<Missed, ScheduleError> {
+        match value {
+            "skip" => Ok(Missed::Skip),
+            "catch-up" => Ok(Missed::CatchUp),
+            _ => Err(invalid(format!("`{}` is not a way to handle missed posts, expected `skip` or `catch-up`", value))),
+        }
+    }
+}
+
+/// Parses durations such as `90s`, `30m`, `6h` or `1d`.
+pub fn parse_duration(text: &str) -> Result<Duration, ScheduleError> {
+    let text = text.trim();
+    let unit = text.chars().last().filter(char::is_ascii_alphabetic);
+    let number = match unit {
+        Some(unit) => &text[..text.len() - unit.len_utf8()],
+        None => text,
+    };
+    let number: u32 = number.parse().map_err(|_| invalid(format!("`{}` is not a duration like `30m` or `6h`", text)))?;
+    let number = i64::from(number);
+
+    match unit {
+        Some('s') => Ok(Duration::seconds(number)),
+        Some('m') | None => Ok(Duration::minutes(number)),
+        Some('h') => Ok(Duration::hours(number)),
+        Some('d') => Ok(Duration::days(number)),
+        Some(unit) => Err(invalid(format!("`{}` is not a unit of time, expected `s`, `m`, `h` or `d`", unit))),
+    }
+}
+
+impl Schedule {
+    /// Reads `--schedule "<cron>"` or `--every <duration>` along with
+    /// `--jitter <duration>`, falling back to `TOOT_SCHEDULE`, `TOOT_EVERY`
+    /// and `TOOT_JITTER`. `None` if no schedule is set.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Schedule>, ScheduleError> {
+        let args: Vec<String> = args.into_iter().collect();
+        let setting = |flag: &str, var: &str| cli::setting(args.clone(), flag, var);
+
+        let timing = match (setting("--schedule", "TOOT_SCHEDULE"), setting("--every", "TOOT_EVERY")) {
+            (Some(_), Some(_)) => return Err(invalid(String::from("set either a cron schedule or an interval, not both"))),
+            (Some(cron), None) => Timing::Cron(cron.parse()?),
+            (None, Some(every)) => {
+                let every = parse_duration(&every)?;
+                if every <= Duration::zero() {
+                    return Err(invalid(String::from("the interval must be longer than zero")));
+                }
+                Timing::Every(every)
+            }
+            (None, None) => return Ok(None),
+        };
+        let jitter = match setting("--jitter", "TOOT_JITTER") {
+            Some(jitter) => parse_duration(&jitter)?,
+            None => Duration::zero(),
+        };
+        Ok(Some(Schedule { timing, jitter }))
+    }
+
+    /// The first slot strictly after `time`, or `None` if there is none,
+    /// as for a cron expression asking for the 31st of February.
+    pub fn next_slot(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match &self.timing {
+            Timing::Every(every) => Some(time + *every),
+            Timing::Cron(cron) => cron.next_after(time),
+        }
+    }
+
+    /// A random delay of up to `jitter`.
+    pub fn jitter<R: Rng + ?Sized>(&self, rng: &mut R) -> Duration {
+        match self.jitter.num_seconds() {
+            seconds if seconds > 0 => Duration::seconds(rng.random_range(0..=seconds)),
+            _ => Duration::zero(),
+        }
+    }
+
+    /// The slot to post at next, given the last post at `last` and the time
+    /// `now`. If a slot was missed in between, `missed` decides whether to
+    /// post now or wait for the next one.
+    pub fn resume(&self, last: DateTime<Utc>, now: DateTime<Utc>, missed: Missed) -> Option<DateTime<Utc>> {
+        let next = self.next_slot(last)?;
+        if next > now {
+            return Some(next);
+        }
+        match missed {
+            Missed::CatchUp => Some(now),
+            Missed::Skip => self.next_slot(now),
+        }
+    }
+}
+
+/// Reads `--missed skip|catch-up` or `TOOT_MISSED`.
+pub fn missed_arg<I: IntoIterator<Item = String>>(args: I) -> Result<Missed, ScheduleError> {
+    match cli::setting(args, "--missed", "TOOT_MISSED") {
+        Some(missed) => missed.parse(),
+        None => Ok(Missed::default()),
+    }
+}
+
+/// The values a cron field allows, as bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Field {
+    bits: u64,
+    /// Written as `*`, which matters for how days of the month and of the
+    /// week combine.
+    any: bool,
+}
+
+impl Field {
+    fn contains(&self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+
+    // Parses lists of `*`, `n`, `a-b`, each optionally with a `/step`.
+    fn parse(text: &str, min: u32, max: u32) -> Result<Field, ScheduleError> {
+        let bad = || invalid(format!("`{}` is not a cron field for values {} to {}", text, min, max));
+        let number = |text: &str| text.parse::<u32>().ok().filter(|n| (min..=max).contains(n)).ok_or_else(bad);
+        let mut bits = 0;
+
+        for item in text.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&step| step > 0).ok_or_else(bad)?),
+                None => (item, 1),
+            };
+            let (from, to) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((from, to)) => (number(from)?, number(to)?),
+                    // `5/15` means every 15 from 5.
+                    None if item.contains('/') => (number(range)?, max),
+                    None => (number(range)?, number(range)?),
+                },
+            };
+            if from > to {
+                return Err(bad());
+            }
+            for value in (from..=to).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(Field { bits, any: text == "*" })
+    }
+}
+
+/// A standard five-field cron expression: minute, hour, day of month, month
+/// and day of week, read in UTC.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    weekdays: Field,
+}
+
+impl FromStr for Cron {
+    type Err = ScheduleError;
+
+    fn from_str(text: &str) -> Result<Cron, ScheduleError> {
+        let expanded = match text.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            text => text,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid(format!("`{}` should have five fields: minute, hour, day, month and weekday", text)));
+        }
+
+        let mut weekdays = Field::parse(fields[4], 0, 7)?;
+        // Both 0 and 7 are Sunday.
+        if weekdays.contains(7) {
+            weekdays.bits |= 1;
+        }
+        Ok(Cron {
+            minutes: Field::parse(fields[0], 0, 59)?,
+            hours: Field::parse(fields[1], 0, 23)?,
+            days: Field::parse(fields[2], 1, 31)?,
+            months: Field::parse(fields[3], 1, 12)?,
+            weekdays,
+        })
+    }
+}
+
+impl Cron {
+    // As in cron, a day restricted by both day of month and day of week
+    // matches if either does.
+    fn day_matches(&self, time: DateTime<Utc>) -> bool {
+        let day = self.days.contains(time.day());
+        let weekday = self.weekdays.contains(time.weekday().num_days_from_sunday());
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `time`, looking up to five
+    /// years ahead.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = time + Duration::days(5 * 366);
+
+        // Skip whole months, days and hours that cannot match rather than
+        // trying every minute.
+        while time < limit {
+            if !self.months.contains(time.month()) {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(time) {
+                time = time.with_hour(0)?.with_minute(0)? + Duration::days(1);
+            } else if !self.hours.contains(time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !self.minutes.contains(time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, day, hour, minute, 0).unwrap()
+    }
+
+    fn cron(text: &str) -> Schedule {
+        Schedule { timing: Timing::Cron(text.parse().unwrap()), jitter: Duration::zero() }
+    }
+
+    #[test]
+    fn it_reads_durations() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::seconds(90)));
+        assert_eq!(parse_duration("30"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_duration("6h"), Ok(Duration::hours(6)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::days(1)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("3w").is_err());
+    }
+
+    #[test]
+    fn it_reads_the_schedule() {
+        let schedule = Schedule::from_args(args(&["toot", "daemon", "--every", "6h", "--jitter=15m"])).unwrap();
+
+        assert_eq!(schedule, Some(Schedule { timing: Timing::Every(Duration::hours(6)), jitter: Duration::minutes(15) }));
+        assert!(Schedule::from_args(args(&["toot", "daemon", "--every", "6h", "--schedule", "@daily"])).is_err());
+        assert!(Schedule::from_args(args(&["toot", "daemon", "--every", "0m"])).is_err());
+        assert_eq!(missed_arg(args(&["toot", "--missed", "catch-up"])), Ok(Missed::CatchUp));
+    }
+
+    #[test]
+    fn it_rejects_bad_cron_expressions() {
+        assert!("* * * *".parse::<Cron>().is_err());
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+        assert!("5-1 * * * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn it_finds_the_next_cron_slot() {
+        // 2024-03-01 was a Friday.
+        assert_eq!(cron("*/15 * * * *").next_slot(at(1, 10, 7)), Some(at(1, 10, 15)));
+        assert_eq!(cron("*/15 * * * *").next_slot(at(1, 10, 15)), Some(at(1, 10, 30)));
+        assert_eq!(cron("30 9,18 * * *").next_slot(at(1, 19, 0)), Some(at(2, 9, 30)));
+        assert_eq!(cron("0 12 * * 1-5").next_slot(at(1, 13, 0)), Some(at(4, 12, 0)));
+        assert_eq!(cron("@monthly").next_slot(at(1, 0, 0)), Some(Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap()));
+        assert_eq!(cron("0 0 29 2 *").next_slot(at(1, 0, 0)), Some(Utc.with_ymd_and_hms(2028, 2, 29, 0, 0, 0).unwrap()));
+        assert_eq!(cron("0 0 31 2 *").next_slot(at(1, 0, 0)), None);
+    }
+
+    #[test]
+    fn it_matches_either_day_when_both_are_given() {
+        // The 15th, or any Sunday.
+        let schedule = cron("0 8 15 * 7");
+
+        assert_eq!(schedule.next_slot(at(1, 9, 0)), Some(at(3, 8, 0)));
+        assert_eq!(schedule.next_slot(at(10, 9, 0)), Some(at(15, 8, 0)));
+    }
+
+    #[test]
+    fn it_jitters_within_bounds() {
+        let schedule = Schedule { timing: Timing::Every(Duration::hours(1)), jitter: Duration::minutes(10) };
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..100 {
+            let jitter = schedule.jitter(&mut rng);
+            assert!(jitter >= Duration::zero() && jitter <= Duration::minutes(10));
+        }
+        assert_eq!(cron("@hourly").jitter(&mut rng), Duration::zero());
+    }
+
+    #[test]
+    fn it_skips_or_catches_up_after_downtime() {
+        let schedule = Schedule { timing: Timing::Every(Duration::hours(6)), jitter: Duration::zero() };
+
+        assert_eq!(schedule.resume(at(1, 0, 0), at(1, 3, 0), Missed::Skip), Some(at(1, 6, 0)));
+        assert_eq!(schedule.resume(at(1, 0, 0), at(2, 3, 0), Missed::Skip), Some(at(2, 9, 0)));
+        assert_eq!(schedule.resume(at(1, 0, 0), at(2, 3, 0), Missed::CatchUp), Some(at(2, 3, 0)));
+    }
+}
@@ -0,0 +1,124 @@
+// Looking cities up by name, forgiving case, accents and small typos.
+
+use super::{remove_diacritics, City};
+
+/// A city matching a `find_city` query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CityMatch<'a> {
+    pub city: &'a City,
+    /// Edits needed to turn the query into the closer of the city's two
+    /// spellings; 0 for an exact match.
+    pub distance: usize,
+}
+
+/// A query split into the name and the provinces or countries narrowing it
+/// down: "Springfield, Illinois" or "Paris, FR".
+#[derive(Debug, PartialEq)]
+pub(crate) struct Query {
+    pub(crate) name: String,
+    pub(crate) qualifiers: Vec<String>,
+}
+
+impl Query {
+    pub(crate) fn parse(query: &str) -> Query {
+        let mut parts = query.split(',').map(fold).filter(|part| !part.is_empty());
+
+        Query {
+            name: parts.next().unwrap_or_default(),
+            qualifiers: parts.collect(),
+        }
+    }
+
+    /// How far `city` is from the query, or `None` if it is too far or does
+    /// not satisfy every qualifier.
+    pub(crate) fn distance(&self, city: &City) -> Option<usize> {
+        let allowed = allowed_edits(&self.name);
+        let distance = [&city.name, &city.city_ascii]
+            .iter()
+            .map(|name| fold(name))
+            // Names differing in length by more than the allowed edits can
+            // never be close enough, so skip working out the distance.
+            .filter(|name| name.chars().count().abs_diff(self.name.chars().count()) <= allowed)
+            .map(|name| edit_distance(&self.name, &name))
+            .min()?;
+        if distance > allowed {
+            return None;
+        }
+        if self.qualifiers.is_empty() {
+            return Some(distance);
+        }
+
+        let places = [&city.province, &city.country, &city.iso2, &city.iso3].map(|place| fold(place));
+        let qualified = self.qualifiers.iter().all(|qualifier| {
+            places.iter().any(|place| !place.is_empty() && edit_distance(qualifier, place) <= allowed_edits(qualifier))
+        });
+        if qualified { Some(distance) } else { None }
+    }
+}
+
+// Short names leave little room for typos before they match something else
+// entirely.
+fn allowed_edits(text: &str) -> usize {
+    match text.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Lower case, without diacritics, and with runs of spaces, hyphens and other
+/// punctuation reduced to a single space.
+pub fn fold(text: &str) -> String {
+    remove_diacritics(text)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Levenshtein distance counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + if a == *b { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_folds_case_accents_and_punctuation() {
+        assert_eq!(fold("  São  Paulo "), "sao paulo");
+        assert_eq!(fold("Winston-Salem"), "winston salem");
+        assert_eq!(fold("ZÜRICH"), "zurich");
+    }
+
+    #[test]
+    fn it_counts_edits() {
+        assert_eq!(edit_distance("pittsburgh", "pittsburgh"), 0);
+        assert_eq!(edit_distance("pitsburgh", "pittsburgh"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn it_splits_qualifiers_from_the_name() {
+        assert_eq!(Query::parse("Springfield, Illinois"), Query {
+            name: String::from("springfield"),
+            qualifiers: vec![String::from("illinois")],
+        });
+        assert_eq!(Query::parse("Paris"), Query { name: String::from("paris"), qualifiers: vec![] });
+    }
+}
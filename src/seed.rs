@@ -0,0 +1,139 @@
+// Reproducible random choices: an explicit seed, or one derived from the date
+// so every instance of the bot picks the same "city of the day".
+
+use chrono::{Datelike, Days, NaiveDate, Utc};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{error, fmt};
+
+use super::cli;
+
+/// Command line flag giving the seed as a number.
+pub const SEED_FLAG: &str = "--seed";
+
+/// Command line flag picking the city of the day for today's date (UTC).
+pub const DAILY_FLAG: &str = "--daily";
+
+/// Command line flag picking the city of the day for another date:
+/// `YYYY-MM-DD`, `today` or `tomorrow`.
+pub const DATE_FLAG: &str = "--date";
+
+#[derive(Debug, PartialEq)]
+pub enum SeedError {
+    Seed(String),
+    Date(String),
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeedError::Seed(value) =>
+                write!(f, "`{}` is not a valid seed, expected a whole number", value),
+            SeedError::Date(value) =>
+                write!(f, "`{}` is not a valid date, expected YYYY-MM-DD, `today` or `tomorrow`", value),
+        }
+    }
+}
+
+impl error::Error for SeedError {}
+
+/// The seed for a day's city: the number of days since 1 January of year 1.
+pub fn daily_seed(date: NaiveDate) -> u64 {
+    date.num_days_from_ce() as u64
+}
+
+pub fn parse_date(value: &str, today: NaiveDate) -> Result<NaiveDate, SeedError> {
+    match value {
+        "today" => Ok(today),
+        "tomorrow" => today.checked_add_days(Days::new(1)).ok_or_else(|| SeedError::Date(String::from(value))),
+        _ => NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| SeedError::Date(String::from(value))),
+    }
+}
+
+/// The seed asked for on the command line, if any: `--seed <n>` wins over
+/// `--date <date>`, which wins over `--daily`.
+pub fn seed_arg<I: IntoIterator<Item = String>>(args: I, today: NaiveDate) -> Result<Option<u64>, SeedError> {
+    let args: Vec<String> = args.into_iter().collect();
+
+    if let Some(seed) = cli::flag_value(args.clone(), SEED_FLAG) {
+        return seed.parse().map(Some).map_err(|_| SeedError::Seed(seed));
+    }
+    if let Some(date) = cli::flag_value(args.clone(), DATE_FLAG) {
+        return parse_date(&date, today).map(|date| Some(daily_seed(date)));
+    }
+    if cli::has_flag(args, DAILY_FLAG) {
+        return Ok(Some(daily_seed(today)));
+    }
+    Ok(None)
+}
+
+/// Like `seed_arg`, taking today's date from the clock.
+pub fn seed_from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<u64>, SeedError> {
+    seed_arg(args, Utc::now().date_naive())
+}
+
+/// Whether the seed follows the date of each post: `--daily` without a
+/// `--seed` or `--date` to pin it.
+pub fn daily_arg<I: IntoIterator<Item = String>>(args: I) -> bool {
+    let args: Vec<String> = args.into_iter().collect();
+
+    cli::has_flag(args.clone(), DAILY_FLAG)
+        && cli::flag_value(args.clone(), SEED_FLAG).is_none()
+        && cli::flag_value(args, DATE_FLAG).is_none()
+}
+
+/// A generator that repeats its choices for the same seed, or an unseeded one.
+///
+/// `StdRng` may produce different numbers in another version of `rand`, so a
+/// seed only picks the same city with the same build and dataset.
+pub fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn it_reads_the_seed() {
+        let today = date(2024, 2, 28);
+
+        assert_eq!(seed_arg(args(&["toot", "--seed", "42"]), today), Ok(Some(42)));
+        assert_eq!(seed_arg(args(&["toot", "--seed=42", "--daily"]), today), Ok(Some(42)));
+        assert_eq!(seed_arg(args(&["toot", "--daily"]), today), Ok(Some(daily_seed(today))));
+        assert_eq!(seed_arg(args(&["toot", "--date", "tomorrow"]), today), Ok(Some(daily_seed(date(2024, 2, 29)))));
+        assert_eq!(seed_arg(args(&["toot", "--date", "2024-03-01"]), today), Ok(Some(daily_seed(date(2024, 3, 1)))));
+        assert_eq!(seed_arg(args(&["toot"]), today), Ok(None));
+    }
+
+    #[test]
+    fn it_follows_the_date_only_for_a_plain_daily_seed() {
+        assert!(daily_arg(args(&["toot", "daemon", "--daily"])));
+        assert!(!daily_arg(args(&["toot", "daemon", "--daily", "--seed", "42"])));
+        assert!(!daily_arg(args(&["toot", "daemon", "--daily", "--date", "2024-03-01"])));
+        assert!(!daily_arg(args(&["toot", "daemon"])));
+    }
+
+    #[test]
+    fn it_rejects_bad_seeds_and_dates() {
+        let today = date(2024, 2, 28);
+
+        assert_eq!(seed_arg(args(&["toot", "--seed", "-1"]), today), Err(SeedError::Seed(String::from("-1"))));
+        assert!(seed_arg(args(&["toot", "--date", "2024-02-30"]), today).is_err());
+        assert!(seed_arg(args(&["toot", "--date", "yesterday"]), today).is_err());
+    }
+
+    #[test]
+    fn it_gives_each_day_its_own_seed() {
+        assert_eq!(daily_seed(date(1, 1, 1)), 1);
+        assert_eq!(daily_seed(date(2024, 3, 1)) - daily_seed(date(2024, 2, 28)), 2);
+    }
+}
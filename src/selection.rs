@@ -0,0 +1,219 @@
+// How the bot picks its next city: which cities qualify, whether bigger ones
+// are more likely, and whether every country or continent gets a fair turn.
+
+use rand::prelude::IndexedRandom;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::{error, fmt};
+
+use super::{cli, City};
+
+/// How likely each qualifying city is to be picked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Weighting {
+    /// Every city equally.
+    #[default]
+    Uniform,
+    /// In proportion to population, so big cities come up most.
+    Population,
+    /// In proportion to the logarithm of population: big cities are favoured,
+    /// but small towns still get a look in.
+    LogPopulation,
+}
+
+/// Groups that take turns, each as likely to be picked as any other no matter
+/// how many cities it has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Stratify {
+    #[default]
+    None,
+    Country,
+    Continent,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    pub weighting: Weighting,
+    pub stratify: Stratify,
+    /// Cities with fewer people than this are never picked.
+    pub min_population: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SelectionError {
+    pub message: String,
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid selection: {}", self.message)
+    }
+}
+
+impl error::Error for SelectionError {}
+
+impl FromStr for Weighting {
+    type Err = SelectionError;
+
+    fn from_str(value: &str) -> Result<Weighting, SelectionError> {
+        match value {
+            "uniform" => Ok(Weighting::Uniform),
+            "population" => Ok(Weighting::Population),
+            "log-population" => Ok(Weighting::LogPopulation),
+            _ => Err(SelectionError {
+                message: format!("`{}` is not a weighting, expected `uniform`, `population` or `log-population`", value),
+            }),
+        }
+    }
+}
+
+impl FromStr for Stratify {
+    type Err = SelectionError;
+
+    fn from_str(value: &str) -> Result<Stratify, SelectionError> {
+        match value {
+            "none" => Ok(Stratify::None),
+            "country" => Ok(Stratify::Country),
+            "continent" => Ok(Stratify::Continent),
+            _ => Err(SelectionError {
+                message: format!("`{}` is not a grouping, expected `none`, `country` or `continent`", value),
+            }),
+        }
+    }
+}
+
+impl Selection {
+    /// Reads `--selection`, `--stratify` and `--min-population`, falling back
+    /// to `TOOT_SELECTION`, `TOOT_STRATIFY` and `TOOT_MIN_POPULATION`.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Selection, SelectionError> {
+        let args: Vec<String> = args.into_iter().collect();
+        let setting = |flag: &str, var: &str| cli::setting(args.clone(), flag, var);
+
+        let mut selection = Selection::default();
+        if let Some(weighting) = setting("--selection", "TOOT_SELECTION") {
+            selection.weighting = weighting.parse()?;
+        }
+        if let Some(stratify) = setting("--stratify", "TOOT_STRATIFY") {
+            selection.stratify = stratify.parse()?;
+        }
+        if let Some(min_population) = setting("--min-population", "TOOT_MIN_POPULATION") {
+            selection.min_population = min_population.parse().map_err(|_| SelectionError {
+                message: format!("`{}` is not a population", min_population),
+            })?;
+        }
+        Ok(selection)
+    }
+
+    /// Picks one of `candidates`, or `None` if none has enough people.
+    pub fn pick<'a, R: Rng + ?Sized>(&self, candidates: Vec<&'a City>, rng: &mut R) -> Option<&'a City> {
+        let candidates: Vec<&City> = candidates
+            .into_iter()
+            .filter(|city| city.population >= self.min_population)
+            .collect();
+
+        let group = match self.stratify {
+            Stratify::None => candidates,
+            Stratify::Country => pick_group(candidates, |city| Some(city.country.clone()), rng)?,
+            Stratify::Continent => pick_group(candidates, City::continent, rng)?,
+        };
+
+        let weight = |city: &&City| match self.weighting {
+            Weighting::Uniform => 1.0,
+            Weighting::Population => city.population,
+            Weighting::LogPopulation => city.population.ln_1p(),
+        };
+        match group.choose_weighted(rng, weight) {
+            Ok(city) => Some(*city),
+            // Every weight was zero: nothing to favour, so pick evenly.
+            Err(_) => group.choose(rng).copied(),
+        }
+    }
+}
+
+// Splits the cities by `key` and returns the cities of one group, each group
+// being equally likely. Cities without a key are left out. The groups are kept
+// sorted so the same seed always picks the same one.
+fn pick_group<'a, K: Ord, R: Rng + ?Sized>(
+    cities: Vec<&'a City>,
+    key: impl Fn(&City) -> Option<K>,
+    rng: &mut R,
+) -> Option<Vec<&'a City>> {
+    let mut groups: BTreeMap<K, Vec<&City>> = BTreeMap::new();
+    for city in cities {
+        if let Some(key) = key(city) {
+            groups.entry(key).or_default().push(city);
+        }
+    }
+
+    let index = (!groups.is_empty()).then(|| rng.random_range(0..groups.len()))?;
+    groups.into_values().nth(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn city(name: &str, population: f64, iso2: &str, iso3: &str, country: &str) -> City {
+        City::new(name, 0.0, 0.0, population, country, "").unwrap().with_iso_codes(iso2, iso3)
+    }
+
+    fn counts(selection: &Selection, cities: &[City]) -> BTreeMap<String, usize> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut counts = BTreeMap::new();
+        for _ in 0..2000 {
+            let picked = selection.pick(cities.iter().collect(), &mut rng).unwrap();
+            *counts.entry(picked.name.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn it_reads_the_selection() {
+        let selection = Selection::from_args(args(&["toot", "--selection", "log-population", "--stratify=continent", "--min-population", "50000"])).unwrap();
+
+        assert_eq!(selection, Selection { weighting: Weighting::LogPopulation, stratify: Stratify::Continent, min_population: 50000.0 });
+        assert!(Selection::from_args(args(&["toot", "--selection", "biggest"])).is_err());
+        assert!(Selection::from_args(args(&["toot", "--min-population", "lots"])).is_err());
+    }
+
+    #[test]
+    fn it_favours_big_cities_by_population() {
+        let cities = [city("Big", 9000.0, "US", "USA", "USA"), city("Small", 1000.0, "US", "USA", "USA")];
+
+        let counts = counts(&Selection { weighting: Weighting::Population, ..Selection::default() }, &cities);
+
+        assert!(counts["Big"] > 1600, "{:?}", counts);
+    }
+
+    #[test]
+    fn it_gives_each_country_a_fair_turn() {
+        let mut cities: Vec<City> = (0..9).map(|i| city(&format!("US {}", i), 100.0, "US", "USA", "USA")).collect();
+        cities.push(city("Andorra la Vella", 100.0, "AD", "AND", "Andorra"));
+
+        let counts = counts(&Selection { stratify: Stratify::Country, ..Selection::default() }, &cities);
+
+        assert!(counts["Andorra la Vella"] > 900, "{:?}", counts);
+    }
+
+    #[test]
+    fn it_leaves_out_small_cities() {
+        let cities = [city("Big", 9000.0, "US", "USA", "USA"), city("Small", 1000.0, "US", "USA", "USA")];
+        let selection = Selection { min_population: 5000.0, stratify: Stratify::Continent, ..Selection::default() };
+
+        assert_eq!(counts(&selection, &cities).keys().collect::<Vec<_>>(), vec!["Big"]);
+        assert_eq!(Selection { min_population: 1e9, ..selection }.pick(cities.iter().collect(), &mut rand::rng()), None);
+    }
+
+    #[test]
+    fn it_picks_evenly_when_nothing_has_weight() {
+        let cities = [city("Empty", 0.0, "AQ", "ATA", "Antarctica")];
+
+        let picked = Selection { weighting: Weighting::Population, ..Selection::default() }.pick(cities.iter().collect(), &mut rand::rng());
+
+        assert_eq!(picked.map(City::name), Some("Empty"));
+    }
+}
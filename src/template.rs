@@ -0,0 +1,468 @@
+// A small placeholder language for the sentences of a location text.
+//
+// `{name}` is replaced by a variable. A list variable on its own is joined
+// with ", "; `{#list ", "}...{/list}` repeats its body for each item instead,
+// joined by the quoted separator, and inside the body the item's own fields
+// (`{name}`, `{province}`, `{country}`, `{flag}`) are available. `{{` and `}}` stand for
+// literal braces.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::{error, fmt, fs};
+
+/// What a variable holds while rendering.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Text(String),
+    List(Vec<HashMap<&'static str, String>>),
+}
+
+pub type Context = HashMap<&'static str, Value>;
+
+/// The field of a list item used when a list is written as a plain `{list}`.
+const DEFAULT_ITEM_FIELD: &str = "name";
+const DEFAULT_LIST_SEPARATOR: &str = ", ";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Variable(String),
+    Each { list: String, separator: String, body: Vec<Node> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateError {
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid template: {}", self.message)
+    }
+}
+
+impl error::Error for TemplateError {}
+
+fn error<T>(message: String) -> Result<T, TemplateError> {
+    Err(TemplateError { message })
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let mut rest = source;
+        let nodes = parse_nodes(&mut rest, None)?;
+
+        Ok(Template { nodes })
+    }
+
+    /// Checks every placeholder refers to something that will exist when
+    /// rendering: `scalars` and `lists` at the top level, plus `fields` inside
+    /// a loop.
+    pub fn check(&self, scalars: &[&str], lists: &[&str], fields: &[&str]) -> Result<(), TemplateError> {
+        check_nodes(&self.nodes, scalars, lists, fields, false)
+    }
+
+    pub fn render(&self, context: &Context) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, context, None, &mut out);
+        out
+    }
+}
+
+fn parse_nodes(rest: &mut &str, closing: Option<&str>) -> Result<Vec<Node>, TemplateError> {
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push(c);
+            *rest = &rest[2..];
+            continue;
+        }
+        if c == '}' {
+            return error(String::from("unmatched `}`"));
+        }
+        if c != '{' {
+            text.push(c);
+            *rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let end = match tag_end(rest) {
+            Some(end) => end,
+            None => return error(format!("unclosed placeholder `{}`", rest)),
+        };
+        let tag = rest[1..end].trim();
+        *rest = &rest[end + 1..];
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(std::mem::take(&mut text)));
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            return match closing {
+                Some(open) if open == name.trim() => Ok(nodes),
+                _ => error(format!("unexpected `{{/{}}}`", name.trim())),
+            };
+        } else if let Some(open) = tag.strip_prefix('#') {
+            let (list, separator) = parse_section_tag(open)?;
+            let body = parse_nodes(rest, Some(&list))?;
+            nodes.push(Node::Each { list, separator, body });
+        } else if is_identifier(tag) {
+            nodes.push(Node::Variable(String::from(tag)));
+        } else {
+            return error(format!("`{{{}}}` is not a variable name", tag));
+        }
+    }
+
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    match closing {
+        Some(open) => error(format!("`{{#{}}}` is never closed", open)),
+        None => Ok(nodes),
+    }
+}
+
+// Where the tag opening `rest` ends: the first `}` outside a quoted
+// separator, so a separator may itself contain braces.
+fn tag_end(rest: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (index, c) in rest.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '}' if !quoted => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+// `#list` or `#list "separator"`
+fn parse_section_tag(tag: &str) -> Result<(String, String), TemplateError> {
+    let tag = tag.trim();
+    let (list, separator) = match tag.find(char::is_whitespace) {
+        Some(space) => (&tag[..space], tag[space..].trim()),
+        None => (tag, ""),
+    };
+    if !is_identifier(list) {
+        return error(format!("`{{#{}}}` is not a list name", tag));
+    }
+
+    let separator = if separator.is_empty() {
+        String::new()
+    } else if separator.len() >= 2 && separator.starts_with('"') && separator.ends_with('"') {
+        String::from(&separator[1..separator.len() - 1])
+    } else {
+        return error(format!("the separator in `{{#{}}}` must be in double quotes", tag));
+    };
+
+    Ok((String::from(list), separator))
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn check_nodes(nodes: &[Node], scalars: &[&str], lists: &[&str], fields: &[&str], in_loop: bool) -> Result<(), TemplateError> {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Variable(name) => {
+                let known = scalars.contains(&name.as_str())
+                    || lists.contains(&name.as_str())
+                    || (in_loop && fields.contains(&name.as_str()));
+                if !known {
+                    return error(format!("unknown variable `{{{}}}`", name));
+                }
+            }
+            Node::Each { list, body, .. } => {
+                if !lists.contains(&list.as_str()) {
+                    return error(format!("unknown list `{{#{}}}`", list));
+                }
+                check_nodes(body, scalars, lists, fields, true)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn render_nodes(nodes: &[Node], context: &Context, item: Option<&HashMap<&'static str, String>>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Variable(name) => {
+                if let Some(value) = item.and_then(|item| item.get(name.as_str())) {
+                    out.push_str(value);
+                    continue;
+                }
+                match context.get(name.as_str()) {
+                    Some(Value::Text(text)) => out.push_str(text),
+                    Some(Value::List(items)) => {
+                        let names = items.iter().filter_map(|item| item.get(DEFAULT_ITEM_FIELD).map(String::as_str));
+                        out.push_str(&names.collect::<Vec<_>>().join(DEFAULT_LIST_SEPARATOR));
+                    }
+                    None => {}
+                }
+            }
+            Node::Each { list, separator, body } => {
+                if let Some(Value::List(items)) = context.get(list.as_str()) {
+                    for (index, item) in items.iter().enumerate() {
+                        if index > 0 {
+                            out.push_str(separator);
+                        }
+                        render_nodes(body, context, Some(item), out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Variables every location template can use.
+pub const SCALARS: &[&str] = &[
+    "city", "province", "country", "flag", "lat_dms", "lon_dms", "lat", "lon",
+    "antipode_city", "antipode_province", "antipode_country", "antipode_km",
+];
+/// Lists of cities along the routes.
+pub const LISTS: &[&str] = &["route_east", "route_north"];
+/// Fields of each city inside a `{#list}` loop.
+pub const FIELDS: &[&str] = &["name", "province", "country", "flag"];
+
+/// Parses a space-separated list of hashtags, such as `Geography {city}`. A
+/// leading `#` is optional, and each tag may use the variables describing the
+/// city but not the route lists.
+pub fn hashtags(spec: &str) -> Result<Vec<Template>, TemplateError> {
+    spec.split_whitespace()
+        .map(|tag| {
+            let template = Template::parse(tag.trim_start_matches('#'))?;
+            template.check(SCALARS, &[], &[])?;
+            Ok(template)
+        })
+        .collect()
+}
+
+/// The sentences making up one wording of a location text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Templates {
+    pub header: Template,
+    pub latitude: Template,
+    pub longitude: Template,
+    pub antipode: Template,
+}
+
+impl Templates {
+    fn sentence_mut(&mut self, sentence: &str) -> Option<&mut Template> {
+        match sentence {
+            "header" => Some(&mut self.header),
+            "latitude" => Some(&mut self.latitude),
+            "longitude" => Some(&mut self.longitude),
+            "antipode" => Some(&mut self.antipode),
+            _ => None,
+        }
+    }
+}
+
+/// The full wording of a location text, and the short one used when a status
+/// would otherwise be too long.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Phrasebook {
+    pub full: Templates,
+    pub short: Templates,
+}
+
+impl Phrasebook {
+    pub fn english() -> Phrasebook {
+        let template = |source: &str| Template::parse(source).unwrap();
+
+        Phrasebook {
+            full: Templates {
+                header: template("You are now in {city}, {province}, {country}\n{lat_dms} {lon_dms}"),
+                latitude: template("If you fly along this latitude in an easterly direction, you will look down on {route_east}."),
+                longitude: template("If you fly along this longitude starting north, you will look down on {route_north}."),
+                antipode: template("Dig straight down and you'll come out near {antipode_city}, {antipode_province}, {antipode_country} ({antipode_km} km from the exact opposite point)."),
+            },
+            short: Templates {
+                header: template("{city}, {province}, {country}\n{lat_dms} {lon_dms}"),
+                latitude: template("East along this latitude: {route_east}."),
+                longitude: template("North along this longitude: {route_north}."),
+                antipode: template("Opposite side: near {antipode_city}, {antipode_country} ({antipode_km} km)."),
+            },
+        }
+    }
+
+    /// Reads a phrasebook file, keeping `base` for any sentence it leaves out.
+    ///
+    /// The file is split into sections by lines naming a sentence in square
+    /// brackets: `[header]`, `[latitude]`, `[longitude]` and `[antipode]` for
+    /// the full wording, or `[short header]` and so on for the short one.
+    /// Everything up to the next section is the template, without the final
+    /// line break. Lines starting with `#` outside a section are comments.
+    ///
+    /// A sentence given in full but not in short is used for both, so a
+    /// status that has to be shortened loses route cities rather than
+    /// switching to `base`'s short wording, which may be in another language.
+    pub fn parse(source: &str, base: &Phrasebook) -> Result<Phrasebook, TemplateError> {
+        let mut phrasebook = base.clone();
+        let mut sections: Vec<(String, Vec<&str>)> = Vec::new();
+        let mut full_only = Vec::new();
+        let mut short = Vec::new();
+
+        for line in source.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                sections.push((String::from(trimmed[1..trimmed.len() - 1].trim()), Vec::new()));
+            } else if let Some((_, lines)) = sections.last_mut() {
+                lines.push(line);
+            } else if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                return error(format!("`{}` is outside any section", trimmed));
+            }
+        }
+
+        for (name, lines) in sections {
+            let template = Template::parse(lines.join("\n").trim_end_matches('\n'))
+                .map_err(|e| TemplateError { message: format!("in [{}]: {}", name, e.message) })?;
+            template.check(SCALARS, LISTS, FIELDS)
+                .map_err(|e| TemplateError { message: format!("in [{}]: {}", name, e.message) })?;
+
+            let (templates, sentence) = match name.strip_prefix("short ") {
+                Some(sentence) => {
+                    short.push(String::from(sentence.trim()));
+                    (&mut phrasebook.short, sentence.trim())
+                }
+                None => {
+                    full_only.push(name.clone());
+                    (&mut phrasebook.full, name.as_str())
+                }
+            };
+            *templates.sentence_mut(sentence).ok_or_else(|| TemplateError {
+                message: format!("unknown section [{}]", name),
+            })? = template;
+        }
+
+        for sentence in full_only.iter().filter(|sentence| !short.contains(sentence)) {
+            if let (Some(full), Some(short)) = (phrasebook.full.sentence_mut(sentence).cloned(), phrasebook.short.sentence_mut(sentence)) {
+                *short = full;
+            }
+        }
+
+        Ok(phrasebook)
+    }
+}
+
+impl Phrasebook {
+    pub fn load_from_path<P: AsRef<Path>>(path: P, base: &Phrasebook) -> Result<Phrasebook, TemplateError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| TemplateError { message: format!("failed to read {}: {}", path.display(), e) })?;
+
+        Phrasebook::parse(&source, base)
+            .map_err(|e| TemplateError { message: format!("{} {}", path.display(), e.message) })
+    }
+}
+
+impl Default for Phrasebook {
+    fn default() -> Phrasebook {
+        Phrasebook::english()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Context {
+        let stop = |name: &str, country: &str| {
+            HashMap::from([("name", String::from(name)), ("country", String::from(country))])
+        };
+        HashMap::from([
+            ("city", Value::Text(String::from("Pittsburgh"))),
+            ("route_east", Value::List(vec![stop("Philadelphia", "USA"), stop("Madrid", "Spain")])),
+        ])
+    }
+
+    #[test]
+    fn it_replaces_variables() {
+        let template = Template::parse("You are now in {city}. {{literal}}").unwrap();
+
+        assert_eq!(template.render(&context()), "You are now in Pittsburgh. {literal}");
+    }
+
+    #[test]
+    fn it_joins_lists() {
+        let template = Template::parse("East: {route_east}.").unwrap();
+
+        assert_eq!(template.render(&context()), "East: Philadelphia, Madrid.");
+    }
+
+    #[test]
+    fn it_loops_over_lists() {
+        let template = Template::parse("{#route_east \" → \"}{name} ({country}){/route_east}").unwrap();
+
+        assert_eq!(template.render(&context()), "Philadelphia (USA) → Madrid (Spain)");
+    }
+
+    #[test]
+    fn it_allows_braces_in_separators() {
+        let template = Template::parse("{#route_east \"} {\"}{name}{/route_east}").unwrap();
+
+        assert_eq!(template.render(&context()), "Philadelphia} {Madrid");
+    }
+
+    #[test]
+    fn it_rejects_malformed_templates() {
+        assert!(Template::parse("{city").is_err());
+        assert!(Template::parse("city}").is_err());
+        assert!(Template::parse("{#route_east}{name}").is_err());
+        assert!(Template::parse("{#route_east}{/route_north}").is_err());
+        assert!(Template::parse("{#route_east , }{name}{/route_east}").is_err());
+        assert!(Template::parse("{not a name}").is_err());
+        assert!(Template::parse("{#route_east \"}{name}{/route_east}").is_err());
+    }
+
+    #[test]
+    fn it_checks_variable_names() {
+        let template = Template::parse("{city} {#route_east \", \"}{name}{/route_east}").unwrap();
+
+        assert!(template.check(&["city"], &["route_east"], &["name"]).is_ok());
+        assert!(template.check(&[], &["route_east"], &["name"]).is_err());
+        assert!(Template::parse("{name}").unwrap().check(&["city"], &["route_east"], &["name"]).is_err());
+    }
+
+    #[test]
+    fn it_reads_a_phrasebook_file() {
+        let source = "# Pirate speak\n[header]\nAhoy from {city}!\n{lat_dms} {lon_dms}\n\n[short latitude]\nEast: {route_east}\n";
+
+        let phrasebook = Phrasebook::parse(source, &Phrasebook::english()).unwrap();
+
+        assert_eq!(phrasebook.full.header, Template::parse("Ahoy from {city}!\n{lat_dms} {lon_dms}").unwrap());
+        assert_eq!(phrasebook.short.latitude, Template::parse("East: {route_east}").unwrap());
+        assert_eq!(phrasebook.full.latitude, Phrasebook::english().full.latitude);
+        // No short header was given, so the pirate one is used for both.
+        assert_eq!(phrasebook.short.header, phrasebook.full.header);
+        assert_eq!(phrasebook.short.longitude, Phrasebook::english().short.longitude);
+    }
+
+    #[test]
+    fn it_rejects_bad_phrasebook_files() {
+        let base = Phrasebook::english();
+
+        assert!(Phrasebook::parse("[footer]\nBye", &base).is_err());
+        assert!(Phrasebook::parse("[header]\n{town}", &base).is_err());
+        assert!(Phrasebook::parse("stray text", &base).is_err());
+    }
+
+    #[test]
+    fn it_parses_hashtags() {
+        let tags = hashtags("#Geography {city}").unwrap();
+
+        assert_eq!(tags, vec![Template::parse("Geography").unwrap(), Template::parse("{city}").unwrap()]);
+        assert!(hashtags("{route_east}").is_err());
+        assert!(hashtags("").unwrap().is_empty());
+    }
+}